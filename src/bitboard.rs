@@ -0,0 +1,201 @@
+use crate::*;
+
+/// One row of a Bitboard, bit x is set when column x is filled
+pub type RowMask = u32;
+
+/// A row where every column is filled
+pub const FULL_ROW: RowMask = (1 << COLUMNS) - 1;
+
+/// Which cells of the board are filled, one integer per row
+///
+/// The colors of the blocks are kept in the grid of GameState, this only knows if a cell is filled or not.
+/// That is all that collisions, line clears and drops need, and checking a bit is much cheaper than comparing cells,
+/// so bots and headless games can ask these questions millions of times.
+///
+/// Rows are counted the same way as the board, the hidden rows first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bitboard {
+    rows: [RowMask; BOARD_ROWS],
+}
+
+impl Bitboard {
+    /// Creates an empty board
+    pub fn new() -> Self {
+        Self { rows: [0; BOARD_ROWS] }
+    }
+
+    /// A getter for the masks of all rows
+    pub fn rows(&self) -> &[RowMask; BOARD_ROWS] {
+        &self.rows
+    }
+
+    /// Checks if a cell is filled, cells outside the board count as filled
+    ///
+    /// Example:
+    ///
+    /// Bitboard::new().filled(3, 5)
+    /// # => false
+    /// Bitboard::new().filled(-1, 5)
+    /// # => true
+    pub fn filled(&self, x: i32, y: i32) -> bool {
+        self.collision(std::iter::once((x, y))).is_some()
+    }
+
+    /// Fills a cell, cells outside the board are ignored
+    pub fn fill(&mut self, x: i32, y: i32) {
+        if (0..COLUMNS as i32).contains(&x) && (0..BOARD_ROWS as i32).contains(&y) {
+            self.rows[y as usize] |= 1 << x;
+        }
+    }
+
+    /// Checks if a row has every column filled
+    pub fn full(&self, y: usize) -> bool {
+        self.rows.get(y) == Some(&FULL_ROW)
+    }
+
+    /// Finds what the cells would collide with if they were placed on the board
+    ///
+    /// Arguments:
+    ///
+    /// cells: impl IntoIterator<Item = (i32, i32)> - The cells in board coordinates, e.g. Player::extent
+    ///
+    /// Return:
+    ///
+    /// Option<Collision> - The first collision that was found, None if all the cells are free
+    ///
+    /// Example:
+    ///
+    /// Bitboard::new().collision([(0, 23), (0, 24)])
+    /// # => Some(Collision::Floor)
+    pub fn collision(&self, cells: impl IntoIterator<Item = (i32, i32)>) -> Option<Collision> {
+        for (x, y) in cells {
+            // nothing can go above the hidden rows
+            if x < 0 || x >= COLUMNS as i32 || y < 0 { return Some(Collision::Wall) }
+            if y >= BOARD_ROWS as i32 { return Some(Collision::Floor) }
+            if self.rows[y as usize] & (1 << x) != 0 { return Some(Collision::Block) }
+        }
+        None
+    }
+
+    /// How many rows the cells can fall before they land
+    ///
+    /// Arguments:
+    ///
+    /// cells: impl Iterator<Item = (i32, i32)> + Clone - The cells in board coordinates, e.g. Player::extent
+    ///
+    /// Return:
+    ///
+    /// i32 - The number of rows, 0 if they are already resting on something or there are no cells
+    ///
+    /// Example:
+    ///
+    /// Bitboard::new().drop_distance([(0, 20), (1, 20)].into_iter())
+    /// # => 3
+    pub fn drop_distance(&self, cells: impl Iterator<Item = (i32, i32)> + Clone) -> i32 {
+        if cells.clone().next().is_none() {
+            return 0;
+        }
+        let mut distance = 0;
+        while self.collision(cells.clone().map(|(x, y)| (x, y + distance + 1))).is_none() {
+            distance += 1;
+        }
+        distance
+    }
+
+    /// Moves row `from` to row `to`, used when cleared rows are removed
+    pub fn copy_row(&mut self, from: usize, to: usize) {
+        self.rows[to] = self.rows[from];
+    }
+
+    /// Empties a row
+    pub fn clear_row(&mut self, y: usize) {
+        self.rows[y] = 0;
+    }
+}
+
+impl Default for Bitboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTTOM: i32 = BOARD_ROWS as i32 - 1;
+
+    #[test]
+    fn cells_outside_the_board_collide() {
+        let mut bitboard = Bitboard::new();
+        bitboard.fill(3, BOTTOM);
+        bitboard.fill(COLUMNS as i32, BOTTOM);
+        bitboard.fill(-1, BOTTOM);
+
+        assert!(matches!(bitboard.collision([(-1, 5)]), Some(Collision::Wall)));
+        assert!(matches!(bitboard.collision([(COLUMNS as i32, 5)]), Some(Collision::Wall)));
+        assert!(matches!(bitboard.collision([(0, -1)]), Some(Collision::Wall)));
+        assert!(matches!(bitboard.collision([(0, BOTTOM + 1)]), Some(Collision::Floor)));
+        assert!(matches!(bitboard.collision([(2, BOTTOM), (3, BOTTOM)]), Some(Collision::Block)));
+        assert!(bitboard.collision([(2, BOTTOM), (4, BOTTOM)]).is_none());
+        assert_eq!(bitboard.rows()[BOTTOM as usize], 1 << 3);
+    }
+
+    #[test]
+    fn rows_are_full_when_every_column_is_filled() {
+        let mut bitboard = Bitboard::new();
+        for x in 0..COLUMNS as i32 - 1 {
+            bitboard.fill(x, BOTTOM);
+        }
+        assert!(!bitboard.full(BOTTOM as usize));
+        bitboard.fill(COLUMNS as i32 - 1, BOTTOM);
+        assert!(bitboard.full(BOTTOM as usize));
+        assert!(!bitboard.full(BOARD_ROWS));
+
+        bitboard.copy_row(BOTTOM as usize, 0);
+        bitboard.clear_row(BOTTOM as usize);
+        assert!(bitboard.full(0));
+        assert!(!bitboard.filled(0, BOTTOM));
+    }
+
+    #[test]
+    fn drop_distance_stops_on_the_highest_block_below() {
+        let mut bitboard = Bitboard::new();
+        assert_eq!(bitboard.drop_distance([(0, 20), (1, 20)].into_iter()), 3);
+        bitboard.fill(1, 22);
+        assert_eq!(bitboard.drop_distance([(0, 20), (1, 20)].into_iter()), 1);
+        assert_eq!(bitboard.drop_distance(std::iter::empty()), 0);
+    }
+
+    /// Clearing lines in a game moves the bitboard the same way as the grid
+    #[test]
+    fn bitboard_follows_the_grid_when_lines_are_cleared() {
+        let mut game = GameState::new();
+        for y in [BOTTOM, BOTTOM - 1] {
+            for x in 2..COLUMNS as i32 {
+                game.grid[y as usize][x as usize] = Cell::Garbage;
+                game.bitboard.fill(x, y);
+            }
+        }
+        game.grid[BOTTOM as usize - 2][5] = Cell::Garbage;
+        game.bitboard.fill(5, BOTTOM - 2);
+
+        let o = game.shapes().iter().position(|shape| shape.name() == 'O').unwrap();
+        game.spawn_shape(o);
+        while game.try_move(PlayerMove::Translate(-1, 0)).is_none() {}
+        game.try_move(PlayerMove::Translate(0, game.drop_distance()));
+        game.kill_player();
+        assert_eq!(game.clearing(), [BOTTOM as usize - 1, BOTTOM as usize]);
+        assert_eq!(game.lines, 2);
+
+        game.clear_lines();
+        let mut expected = Bitboard::new();
+        expected.fill(5, BOTTOM);
+        assert_eq!(game.bitboard, expected);
+        for (y, row) in game.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                assert_eq!(*cell != Cell::Empty, game.bitboard.filled(x as i32, y as i32), "({x}, {y})");
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while the game is running
+///
+/// Variants:
+/// Io(String, io::Error) - A file could not be read or written, the string is the path of the file
+/// Parse(String, json::Error) - A file is not valid json, the string is the path of the file
+/// Terminal(io::Error) - The terminal could not be drawn to or read from
+/// Config(String) - A setting is missing or has a value which can't be used
+/// Data(String, String) - A piece set or a theme is written wrong, the first string says which one and the second what is wrong
+/// Save(String) - The saved game is missing something or doesn't fit the pieces it was saved with
+/// Fumen(String) - A fumen can't be read or put on the board
+#[derive(Debug)]
+pub enum TetrisError {
+    Io(String, io::Error),
+    Parse(String, json::Error),
+    Terminal(io::Error),
+    Config(String),
+    Data(String, String),
+    Save(String),
+    Fumen(String),
+}
+
+impl TetrisError {
+    /// The exit code the program ends with when this error stops it, following the codes in sysexits.h
+    ///
+    /// Example:
+    ///
+    /// TetrisError::Config("difficulty is missing".to_string()).exit_code()
+    /// # => 78
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            TetrisError::Io(_, _) => 74,
+            TetrisError::Parse(_, _) | TetrisError::Data(_, _) | TetrisError::Save(_) | TetrisError::Fumen(_) => 65,
+            TetrisError::Terminal(_) => 71,
+            TetrisError::Config(_) => 78,
+        }
+    }
+}
+
+impl fmt::Display for TetrisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TetrisError::Io(path, error) => write!(f, "could not access {path}: {error}"),
+            TetrisError::Parse(path, error) => write!(f, "{path} is not valid json: {error}"),
+            TetrisError::Terminal(error) => write!(f, "the terminal stopped working: {error}"),
+            TetrisError::Config(message) => write!(f, "invalid settings: {message}"),
+            TetrisError::Data(what, message) => write!(f, "{what} is written wrong: {message}"),
+            TetrisError::Save(message) => write!(f, "the saved game can't be continued: {message}"),
+            TetrisError::Fumen(message) => write!(f, "the fumen can't be used: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TetrisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TetrisError::Io(_, error) | TetrisError::Terminal(error) => Some(error),
+            TetrisError::Parse(_, error) => Some(error),
+            TetrisError::Config(_) | TetrisError::Data(_, _) | TetrisError::Save(_) | TetrisError::Fumen(_) => None,
+        }
+    }
+}
+
+/// Crossterm reports its errors as io::Error, so every io::Error that doesn't come from a file is a terminal error
+impl From<io::Error> for TetrisError {
+    fn from(error: io::Error) -> Self {
+        TetrisError::Terminal(error)
+    }
+}
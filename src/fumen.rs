@@ -0,0 +1,474 @@
+use crate::*;
+
+/// The file in the data directory the board is written to when it is exported during a game
+pub const FUMEN_FILE: &str = "fumen.txt";
+
+/// The characters fumen uses to write numbers, every character is a digit in base 64
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Every fumen of the version that is supported starts with this
+const PREFIX: &str = "v115@";
+
+/// The size of a fumen field. The last row is the garbage row under the field, it is never used here.
+const FUMEN_COLUMNS: usize = 10;
+const FUMEN_ROWS: usize = 24;
+/// The rows of a fumen field above the garbage row
+const FIELD_ROWS: usize = 23;
+/// The fumen field covers the middle columns and the lowest rows of the board
+const LEFT: usize = (COLUMNS - FUMEN_COLUMNS) / 2;
+const TOP: usize = BOARD_ROWS - FIELD_ROWS;
+
+/// The pieces of fumen in the order of their numbers, 0 is an empty cell and 8 is a gray garbage block
+const PIECE_NAMES: [char; 9] = [' ', 'I', 'L', 'O', 'Z', 'T', 'J', 'S', 'X'];
+const GARBAGE: u8 = 8;
+
+/// The blocks of every fumen piece in its spawn rotation around the point that the fumen stores, with y going up
+const BLOCKS: [[(i32, i32); 4]; 8] = [
+    [(0, 0); 4],
+    [(0, 0), (-1, 0), (1, 0), (2, 0)],
+    [(0, 0), (-1, 0), (1, 0), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (1, 1)],
+    [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    [(0, 0), (-1, 0), (1, 0), (0, 1)],
+    [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+    [(0, 0), (-1, 0), (0, 1), (1, 1)],
+];
+
+/// The rotations of fumen in the order of their numbers, as quarter turns clockwise from the way the piece spawns
+const ROTATIONS: [i32; 4] = [2, 1, 0, 3];
+
+/// A piece on a fumen page
+///
+/// Fields:
+/// kind: u8 - The fumen number of the piece, 1 to 7
+/// rotation: u8 - The fumen number of the rotation, 0 is upside down, 1 is right, 2 is spawn and 3 is left
+/// x: i32 - The column of the point the fumen stores
+/// y: i32 - The row of the point the fumen stores, counted from the bottom row of the field
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct FumenPiece {
+    kind: u8,
+    rotation: u8,
+    x: i32,
+    y: i32,
+}
+
+impl FumenPiece {
+    /// Fumen stores the I, O, S and Z pieces from another point than the one they rotate around in some rotations,
+    /// so that a piece turned half a turn is stored the same way. This is how far that point is from the one in BLOCKS.
+    fn correction(&self) -> (i32, i32) {
+        match (PIECE_NAMES[self.kind as usize], self.rotation) {
+            ('O', 0) => (1, 0),
+            ('O', 2) => (0, -1),
+            ('O', 3) => (1, -1),
+            ('I', 0) => (1, 0),
+            ('I', 3) => (0, -1),
+            ('S', 1) => (-1, 0),
+            ('S', 2) => (0, -1),
+            ('Z', 2) => (0, -1),
+            ('Z', 3) => (1, 0),
+            _ => (0, 0),
+        }
+    }
+
+    /// The blocks of the piece around (0, 0), with y going up
+    fn blocks(kind: u8, rotation: u8) -> [(i32, i32); 4] {
+        BLOCKS[kind as usize].map(|(x, y)| match rotation {
+            0 => (-x, -y),
+            1 => (y, -x),
+            3 => (-y, x),
+            _ => (x, y),
+        })
+    }
+
+    /// The cells of the piece on the board, sorted
+    fn cells(&self) -> Vec<(i32, i32)> {
+        let (dx, dy) = self.correction();
+        let mut cells: Vec<(i32, i32)> = Self::blocks(self.kind, self.rotation)
+            .iter()
+            .map(|&(x, y)| board_position(self.x + dx + x, self.y + dy + y))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Finds the fumen piece that covers the cells on the board
+    ///
+    /// Arguments:
+    ///
+    /// kind: u8 - The fumen number of the piece
+    /// rotation: i32 - Quarter turns clockwise from spawn
+    /// cells: impl Iterator<Item = (i32, i32)> - The cells on the board
+    ///
+    /// Return:
+    ///
+    /// Option<FumenPiece> - The piece, or None if the cells aren't that piece or it is outside the fumen field
+    fn find(kind: u8, rotation: i32, cells: impl Iterator<Item = (i32, i32)>) -> Option<FumenPiece> {
+        let rotation = ROTATIONS.iter().position(|&turns| turns == rotation.rem_euclid(4))? as u8;
+        let mut cells: Vec<(i32, i32)> = cells.collect();
+        cells.sort_unstable();
+        let blocks = Self::blocks(kind, rotation);
+        // the point the fumen stores is as far from the lowest and leftmost cell as it is in BLOCKS
+        let (x, y) = cells.iter().map(|&(x, y)| fumen_position(x, y)).fold((i32::MAX, i32::MAX), |(a, b), (x, y)| (a.min(x), b.min(y)));
+        let x = x - blocks.iter().map(|&(x, _)| x).min()?;
+        let y = y - blocks.iter().map(|&(_, y)| y).min()?;
+        let mut piece = FumenPiece { kind, rotation, x, y };
+        let (dx, dy) = piece.correction();
+        piece.x -= dx;
+        piece.y -= dy;
+        let inside = (0..FUMEN_COLUMNS as i32).contains(&piece.x) && (0..FIELD_ROWS as i32).contains(&piece.y);
+        (inside && piece.cells() == cells).then_some(piece)
+    }
+}
+
+/// Turns a column and a row counted from the bottom of the fumen field into a position on the board
+fn board_position(x: i32, y: i32) -> (i32, i32) {
+    (x + LEFT as i32, (TOP + FIELD_ROWS) as i32 - 1 - y)
+}
+
+/// Turns a position on the board into a column and a row counted from the bottom of the fumen field
+fn fumen_position(x: i32, y: i32) -> (i32, i32) {
+    (x - LEFT as i32, (TOP + FIELD_ROWS) as i32 - 1 - y)
+}
+
+/// The first page of a fumen, the format the Tetris community shares boards in
+///
+/// Only fumen version 115 (strings starting with v115@) is supported, and only the first page is read.
+/// The field of a fumen is 10 columns wide, it is placed in the middle of the board and at the bottom of it.
+/// The board is wider than that, so the columns on each side of the field are filled with garbage when a fumen is put on the board.
+/// They are walls that make the board as wide as the fumen, so that its rows can be cleared. The walls move down with cleared rows
+/// like every other block, so after many cleared rows the columns open up from the top.
+///
+/// Fields:
+/// field: [[u8; FUMEN_COLUMNS]; FUMEN_ROWS] - The fumen number of the piece in every cell, 0 is empty. The top row comes first
+/// piece: Option<FumenPiece> - The piece on the page, if there is one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fumen {
+    field: [[u8; FUMEN_COLUMNS]; FUMEN_ROWS],
+    piece: Option<FumenPiece>,
+}
+
+impl Fumen {
+    /// Reads a fumen. It can be a link, everything before v115@ is skipped.
+    ///
+    /// Arguments:
+    ///
+    /// text: &str - The fumen
+    ///
+    /// Return:
+    ///
+    /// Result<Fumen, TetrisError> - The first page, or an error if the text isn't a fumen that can be read
+    ///
+    /// Example:
+    ///
+    /// Fumen::parse("v115@vhAAgH")
+    /// # => Ok(Fumen{field: [[0; 10]; 24], piece: None})
+    pub fn parse(text: &str) -> Result<Fumen, TetrisError> {
+        let start = text
+            .find(PREFIX)
+            .ok_or_else(|| TetrisError::Fumen(format!("it doesn't start with {PREFIX}")))?;
+        // links sometimes have a ? in the middle of the data, it is not a part of it
+        let mut digits = text[start + PREFIX.len()..]
+            .trim()
+            .bytes()
+            .filter(|&char| char != b'?')
+            .map(|char| ALPHABET.iter().position(|&digit| digit == char));
+        let mut read = |length: u32| -> Result<usize, TetrisError> {
+            (0..length).try_fold(0, |value, place| match digits.next() {
+                Some(Some(digit)) => Ok(value + digit * 64usize.pow(place)),
+                Some(None) => Err(TetrisError::Fumen("it has a character that isn't a part of fumen".to_string())),
+                None => Err(TetrisError::Fumen("it ends too early".to_string())),
+            })
+        };
+
+        // the field is written as runs of cells that have the same value, which is 8 more than the change from the page before.
+        // There is no page before the first one, so the value is the piece plus 8.
+        let mut cells = [0; FUMEN_COLUMNS * FUMEN_ROWS];
+        let mut index = 0;
+        while index < cells.len() {
+            let run = read(2)?;
+            let (value, length) = (run / cells.len(), run % cells.len() + 1);
+            if !(8..=8 + GARBAGE as usize).contains(&value) || index + length > cells.len() {
+                return Err(TetrisError::Fumen("the field is broken".to_string()));
+            }
+            cells[index..index + length].fill((value - 8) as u8);
+            index += length;
+            // a page where nothing changed is followed by how many pages after it that doesn't change either
+            if length == cells.len() && value == 8 {
+                read(1)?;
+            }
+        }
+
+        let mut action = read(3)?;
+        let kind = (action % 8) as u8;
+        action /= 8;
+        let rotation = (action % 4) as u8;
+        action /= 4;
+        let coordinate = action % (FUMEN_COLUMNS * FUMEN_ROWS);
+        // a piece in the garbage row can't be placed on the board
+        let piece = (kind > 0 && kind < GARBAGE && coordinate < FUMEN_COLUMNS * FIELD_ROWS).then_some(FumenPiece {
+            kind,
+            rotation,
+            x: (coordinate % FUMEN_COLUMNS) as i32,
+            y: (FIELD_ROWS - 1 - coordinate / FUMEN_COLUMNS) as i32,
+        });
+
+        let mut field = [[0; FUMEN_COLUMNS]; FUMEN_ROWS];
+        for (row, values) in field.iter_mut().zip(cells.chunks(FUMEN_COLUMNS)) {
+            row.copy_from_slice(values);
+        }
+        Ok(Fumen { field, piece })
+    }
+
+    /// Writes the page as a fumen
+    ///
+    /// Example:
+    ///
+    /// Fumen::parse("v115@vhAAgH")?.encode()
+    /// # => "v115@vhAAgH"
+    pub fn encode(&self) -> String {
+        let mut digits = Vec::new();
+        let mut write = |mut value: usize, length: u32| {
+            for _ in 0..length {
+                digits.push(ALPHABET[value % 64]);
+                value /= 64;
+            }
+        };
+
+        let cells: Vec<u8> = self.field.iter().flatten().copied().collect();
+        let mut index = 0;
+        while index < cells.len() {
+            let length = cells[index..].iter().take_while(|&&cell| cell == cells[index]).count();
+            write((cells[index] as usize + 8) * cells.len() + length - 1, 2);
+            if length == cells.len() && cells[index] == 0 {
+                write(0, 1);
+            }
+            index += length;
+        }
+
+        // the flags after the coordinate are: no rise, no mirror, colors on, no comment and the piece locks
+        let (kind, rotation, coordinate) = match self.piece {
+            Some(piece) => (piece.kind, piece.rotation, (FIELD_ROWS - 1 - piece.y as usize) * FUMEN_COLUMNS + piece.x as usize),
+            None => (0, 0, 0),
+        };
+        let flags = 0b00100;
+        write(kind as usize + 8 * (rotation as usize + 4 * (coordinate + FUMEN_COLUMNS * FUMEN_ROWS * flags)), 3);
+
+        let digits = String::from_utf8(digits).unwrap_or_default();
+        format!("{PREFIX}{digits}")
+    }
+
+    /// Makes a page of the board and the player of a game.
+    /// If the player isn't one of the seven tetrominoes it is drawn as blocks in the field instead. Blocks of pieces that aren't tetrominoes become gray garbage.
+    /// Garbage outside the fumen field is left out, it is the walls apply() put there.
+    ///
+    /// Arguments:
+    ///
+    /// game: &GameState - The game
+    ///
+    /// Return:
+    ///
+    /// Result<Fumen, TetrisError> - The page, or an error if a piece or the player is outside the fumen field and would be lost
+    pub fn from_game(game: &GameState) -> Result<Fumen, TetrisError> {
+        let mut field = [[0; FUMEN_COLUMNS]; FUMEN_ROWS];
+        let mut draw = |x: i32, y: i32, value: u8| -> bool {
+            let (x, y) = fumen_position(x, y);
+            let inside = (0..FUMEN_COLUMNS as i32).contains(&x) && (0..FIELD_ROWS as i32).contains(&y);
+            if inside {
+                field[FIELD_ROWS - 1 - y as usize][x as usize] = value;
+            }
+            inside
+        };
+        let mut outside = false;
+        for (y, row) in game.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                match cell {
+                    Cell::Piece(index) => outside |= !draw(x as i32, y as i32, Self::value(&game.shapes[*index]).unwrap_or(GARBAGE)),
+                    Cell::Garbage => {
+                        draw(x as i32, y as i32, GARBAGE);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let value = Self::value(&game.shapes[game.current]);
+        let piece = game.player.as_ref().and_then(|player| FumenPiece::find(value?, player.rotation(), player.extent()));
+        if let (None, Some(player)) = (piece, &game.player) {
+            for (x, y) in player.extent() {
+                outside |= !draw(x, y, value.unwrap_or(GARBAGE));
+            }
+        }
+        if outside {
+            return Err(TetrisError::Fumen(format!("there are blocks outside the {FUMEN_COLUMNS} middle columns, a fumen can't hold them")));
+        }
+        Ok(Fumen { field, piece })
+    }
+
+    /// Puts the page on the board of a game, the blocks of the fumen replace the board. The columns beside the fumen field are filled with garbage
+    /// from the top of the field and down. The piece of the page becomes the player.
+    ///
+    /// Arguments:
+    ///
+    /// game: &mut GameState - The game, it should not have started yet
+    ///
+    /// Return:
+    ///
+    /// Result<(), TetrisError> - An error if the piece of the page isn't one of the game's pieces or overlaps the field
+    pub fn apply(&self, game: &mut GameState) -> Result<(), TetrisError> {
+        game.grid = [[Cell::Empty; COLUMNS]; BOARD_ROWS];
+        game.bitboard = Bitboard::new();
+        for y in TOP..BOARD_ROWS {
+            for x in (0..LEFT).chain(LEFT + FUMEN_COLUMNS..COLUMNS) {
+                game.grid[y][x] = Cell::Garbage;
+                game.bitboard.fill(x as i32, y as i32);
+            }
+        }
+        for (row, values) in self.field.iter().take(FIELD_ROWS).enumerate() {
+            for (column, &value) in values.iter().enumerate() {
+                if value == 0 {
+                    continue;
+                }
+                let (x, y) = board_position(column as i32, (FIELD_ROWS - 1 - row) as i32);
+                let index = game.shapes.iter().position(|shape| Self::value(shape) == Some(value));
+                game.grid[y as usize][x as usize] = index.map_or(Cell::Garbage, Cell::Piece);
+                game.bitboard.fill(x, y);
+            }
+        }
+
+        let Some(piece) = self.piece else {
+            return Ok(());
+        };
+        let name = PIECE_NAMES[piece.kind as usize];
+        let mismatch = || TetrisError::Fumen(format!("the {name} piece of the fumen is not one of the pieces that are played with"));
+        let index = game.shapes.iter().position(|shape| Self::value(shape) == Some(piece.kind)).ok_or_else(mismatch)?;
+        let shape = &game.shapes[index];
+        let cells = piece.cells();
+        // the player is put where its lowest and leftmost cell is on the lowest and leftmost cell of the fumen piece
+        let mut player = Player::spawn(0, 0, shape.spawn_orientation());
+        player.rotate(ROTATIONS[piece.rotation as usize]);
+        let (x, y) = player.extent().fold((i32::MAX, i32::MAX), |(a, b), (x, y)| (a.min(x), b.min(y)));
+        let (to_x, to_y) = cells.iter().fold((i32::MAX, i32::MAX), |(a, b), &(x, y)| (a.min(x), b.min(y)));
+        player.translate(to_x - x, to_y - y);
+        let mut placed: Vec<(i32, i32)> = player.extent().collect();
+        placed.sort_unstable();
+        if placed != cells {
+            return Err(mismatch());
+        }
+        game.current = index;
+        game.player = Some(player);
+        if game.collision().is_some() {
+            game.player = None;
+            return Err(TetrisError::Fumen(format!("the {name} piece of the fumen overlaps the field")));
+        }
+        Ok(())
+    }
+
+    /// The fumen number of a shape. Only tetrominoes have one, a pentomino named I is not the I of fumen.
+    fn value(shape: &Shape) -> Option<u8> {
+        if shape.extent().len() != 4 {
+            return None;
+        }
+        PIECE_NAMES[1..GARBAGE as usize].iter().position(|&name| name == shape.name()).map(|index| index as u8 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The empty field, as the fumen editor writes it
+    const EMPTY: &str = "v115@vhAAgH";
+    /// Four rows with the six left columns gray at the bottom of the field
+    const GARBAGE_ROWS: &str = "v115@9gF8DeF8DeF8DeF8NeAgH";
+    /// A T in spawn rotation on the bottom row, its center in the fifth column
+    const T_SPAWN: &str = "v115@vhAVQJ";
+
+    #[test]
+    fn empty_field_round_trips() {
+        let fumen = Fumen::parse(EMPTY).unwrap();
+        assert_eq!(fumen, Fumen { field: [[0; FUMEN_COLUMNS]; FUMEN_ROWS], piece: None });
+        assert_eq!(fumen.encode(), EMPTY);
+    }
+
+    #[test]
+    fn links_are_read_from_the_prefix() {
+        let fumen = Fumen::parse(&format!("https://harddrop.com/fumen/?{EMPTY}")).unwrap();
+        assert_eq!(fumen.encode(), EMPTY);
+    }
+
+    #[test]
+    fn garbage_rows_round_trip() {
+        let fumen = Fumen::parse(GARBAGE_ROWS).unwrap();
+        for (row, values) in fumen.field.iter().enumerate() {
+            let expected: [u8; FUMEN_COLUMNS] = match row {
+                19..=22 => [8, 8, 8, 8, 8, 8, 0, 0, 0, 0],
+                _ => [0; FUMEN_COLUMNS],
+            };
+            assert_eq!(*values, expected, "row {row}");
+        }
+        assert_eq!(fumen.encode(), GARBAGE_ROWS);
+    }
+
+    #[test]
+    fn piece_is_read_from_the_bottom_of_the_field() {
+        let fumen = Fumen::parse(T_SPAWN).unwrap();
+        assert_eq!(fumen.piece, Some(FumenPiece { kind: 5, rotation: 2, x: 4, y: 0 }));
+        assert_eq!(fumen.encode(), T_SPAWN);
+
+        // the T points up, so the bottom row of the board has three blocks and the row above it one
+        let mut game = GameState::new();
+        fumen.apply(&mut game).unwrap();
+        let mut cells: Vec<(i32, i32)> = game.player().unwrap().extent().collect();
+        cells.sort_unstable();
+        let bottom = BOARD_ROWS as i32 - 1;
+        let center = LEFT as i32 + 4;
+        assert_eq!(cells, vec![(center - 1, bottom), (center, bottom - 1), (center, bottom), (center + 1, bottom)]);
+    }
+
+    #[test]
+    fn every_piece_and_rotation_round_trips_through_a_game() {
+        for kind in 1..GARBAGE {
+            for rotation in 0..4 {
+                let fumen = Fumen { field: [[0; FUMEN_COLUMNS]; FUMEN_ROWS], piece: Some(FumenPiece { kind, rotation, x: 4, y: 5 }) };
+                let mut game = GameState::new();
+                fumen.apply(&mut game).unwrap();
+                let name = PIECE_NAMES[kind as usize];
+                assert_eq!(game.shapes[game.current].name(), name);
+                assert_eq!(Fumen::from_game(&game).unwrap(), fumen, "{name} in rotation {rotation}");
+                assert_eq!(Fumen::parse(&fumen.encode()).unwrap(), fumen, "{name} in rotation {rotation}");
+            }
+        }
+    }
+
+    #[test]
+    fn apply_walls_off_the_columns_beside_the_field() {
+        let mut game = GameState::new();
+        Fumen::parse(GARBAGE_ROWS).unwrap().apply(&mut game).unwrap();
+        for y in TOP..BOARD_ROWS {
+            for x in (0..LEFT).chain(LEFT + FUMEN_COLUMNS..COLUMNS) {
+                assert!(game.grid[y][x] == Cell::Garbage, "({x}, {y})");
+            }
+        }
+        // the lowest row is gray in the six left columns of the field and empty in the rest
+        let bottom = BOARD_ROWS - 1;
+        assert!(game.grid[bottom][LEFT..LEFT + 6].iter().all(|&cell| cell == Cell::Garbage));
+        assert!(game.grid[bottom][LEFT + 6..LEFT + FUMEN_COLUMNS].iter().all(|&cell| cell == Cell::Empty));
+        assert_eq!(Fumen::from_game(&game).unwrap().encode(), GARBAGE_ROWS);
+    }
+
+    #[test]
+    fn export_refuses_blocks_outside_the_field() {
+        let mut game = GameState::new();
+        game.grid[BOARD_ROWS - 1][0] = Cell::Piece(0);
+        assert!(Fumen::from_game(&game).is_err());
+    }
+
+    #[test]
+    fn pieces_that_are_not_tetrominoes_become_garbage() {
+        let shapes = PieceSet::Pentominoes.shapes().unwrap();
+        let index = shapes.iter().position(|shape| shape.name() == 'I').unwrap();
+        let mut game = GameState::with_pieces(shapes, RandomizerKind::SevenBag.create());
+        game.grid[BOARD_ROWS - 1][LEFT] = Cell::Piece(index);
+        let fumen = Fumen::from_game(&game).unwrap();
+        assert_eq!(fumen.field[FIELD_ROWS - 1][0], GARBAGE);
+    }
+}
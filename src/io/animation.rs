@@ -0,0 +1,137 @@
+use crate::*;
+
+/// En animation som ritas ovanpå spelplanen
+///
+/// Animationer ändrar aldrig spelet, de ändrar bara vilka celler output ritar medan de pågår. När en animation är slut ritas cellerna som vanligt igen. Rader och celler räknas på hela spelplanen, med de gömda raderna överst.
+///     Clear: raderna som rensas blinkar och löses sedan upp från mitten och utåt
+///     Lock: cellerna där en bit precis låstes blinkar till
+///     Trail: cellerna som en bit föll igenom vid ett hard drop lyser upp en kort stund
+#[derive(Clone, PartialEq, Debug)]
+pub enum Animation {
+    Clear(Vec<usize>),
+    Lock(Vec<(usize, usize)>),
+    Trail(Vec<(usize, usize)>),
+}
+
+impl Animation {
+    /// Denna funktion ger hur länge animationen varar i normal hastighet.
+    pub fn length(&self) -> Duration {
+        match self {
+            Animation::Clear(_) => Duration::from_millis(400),
+            Animation::Lock(_) => Duration::from_millis(120),
+            Animation::Trail(_) => Duration::from_millis(150),
+        }
+    }
+
+    /// Denna funktion ritar animationen på en spelplan.
+    ///
+    /// Argument 1(self): &Animation - animationen
+    /// Argument 2(grid): &mut [[Cell;COLUMNS];BOARD_ROWS] - hela spelplanen inklusive de gömda raderna, den ändras direkt
+    /// Argument 3(progress): f32 - hur långt animationen har kommit, från 0 till 1
+    ///
+    /// Exempel:
+    ///     Animation::Clear(vec![23]).apply(&mut grid, 0.0); -> rad 23 blir Cell::Flash
+    ///     Animation::Clear(vec![23]).apply(&mut grid, 0.9); -> bara kanterna av rad 23 är kvar
+    pub fn apply(&self, grid: &mut [[Cell;COLUMNS];BOARD_ROWS], progress: f32) {
+        match self {
+            Animation::Clear(rows) => {
+                for &y in rows.iter().filter(|&&y| y < BOARD_ROWS) {
+                    if progress < 0.5 {
+                        // första halvan blinkar raden fyra gånger
+                        if ((progress * 16.0) as usize).is_multiple_of(2) {
+                            grid[y] = [Cell::Flash;COLUMNS];
+                        }
+                    } else {
+                        // andra halvan försvinner cellerna från mitten och utåt
+                        let radius = (progress - 0.5) * 2.0 * COLUMNS as f32 / 2.0;
+                        for (x, cell) in grid[y].iter_mut().enumerate() {
+                            if (x as f32 + 0.5 - COLUMNS as f32 / 2.0).abs() < radius {
+                                *cell = Cell::Empty;
+                            }
+                        }
+                    }
+                }
+            }
+            Animation::Lock(cells) => {
+                for &(x, y) in cells {
+                    if x < COLUMNS && y < BOARD_ROWS {
+                        grid[y][x] = Cell::Flash;
+                    }
+                }
+            }
+            Animation::Trail(cells) => {
+                // svansen krymper uppifrån medan animationen pågår
+                let skip = (cells.len() as f32 * progress) as usize;
+                for &(x, y) in cells.iter().skip(skip) {
+                    if x < COLUMNS && y < BOARD_ROWS && grid[y][x] == Cell::Empty {
+                        grid[y][x] = Cell::Flash;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// En spelplan där den nedersta raden är full, så att det syns vad som rensas
+    fn full_bottom_row() -> [[Cell;COLUMNS];BOARD_ROWS] {
+        let mut grid = [[Cell::Empty;COLUMNS];BOARD_ROWS];
+        grid[BOARD_ROWS - 1] = [Cell::Garbage;COLUMNS];
+        grid
+    }
+
+    #[test]
+    fn clear_blinks_the_rows_first() {
+        let clear = Animation::Clear(vec![BOARD_ROWS - 1]);
+        let mut grid = full_bottom_row();
+        clear.apply(&mut grid, 0.0);
+        assert!(grid[BOARD_ROWS - 1] == [Cell::Flash;COLUMNS]);
+        assert!(grid[BOARD_ROWS - 2] == [Cell::Empty;COLUMNS]);
+
+        let mut grid = full_bottom_row();
+        clear.apply(&mut grid, 0.1);
+        assert!(grid[BOARD_ROWS - 1] == [Cell::Garbage;COLUMNS]);
+    }
+
+    #[test]
+    fn clear_empties_the_rows_from_the_middle_out() {
+        let clear = Animation::Clear(vec![BOARD_ROWS - 1]);
+        let mut grid = full_bottom_row();
+        clear.apply(&mut grid, 0.75);
+        for (x, &cell) in grid[BOARD_ROWS - 1].iter().enumerate() {
+            let middle = (COLUMNS / 4..COLUMNS - COLUMNS / 4).contains(&x);
+            assert!(cell == if middle { Cell::Empty } else { Cell::Garbage }, "column {x}");
+        }
+
+        let mut grid = full_bottom_row();
+        clear.apply(&mut grid, 1.0);
+        assert!(grid[BOARD_ROWS - 1] == [Cell::Empty;COLUMNS]);
+    }
+
+    #[test]
+    fn lock_flashes_the_cells_and_ignores_cells_outside() {
+        let mut grid = full_bottom_row();
+        Animation::Lock(vec![(0, 0), (3, 5), (COLUMNS, 0), (0, BOARD_ROWS)]).apply(&mut grid, 0.5);
+        assert!(grid[0][0] == Cell::Flash);
+        assert!(grid[5][3] == Cell::Flash);
+        assert_eq!(grid.iter().flatten().filter(|&&cell| cell == Cell::Flash).count(), 2);
+    }
+
+    #[test]
+    fn trail_shrinks_from_the_top_and_only_covers_empty_cells() {
+        let trail = Animation::Trail(vec![(2, 20), (2, 21), (2, 22), (2, 23)]);
+        let mut grid = full_bottom_row();
+        trail.apply(&mut grid, 0.0);
+        assert!(grid[20][2] == Cell::Flash && grid[22][2] == Cell::Flash);
+        // den nedersta raden är redan fylld och ritas inte över
+        assert!(grid[23][2] == Cell::Garbage);
+
+        let mut grid = full_bottom_row();
+        trail.apply(&mut grid, 0.5);
+        assert!(grid[20][2] == Cell::Empty && grid[21][2] == Cell::Empty);
+        assert!(grid[22][2] == Cell::Flash);
+    }
+}
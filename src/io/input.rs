@@ -1,50 +1,232 @@
-use std::time::Duration;
+use std::io;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{poll, read, Event};
+use crossterm::event::{read, Event};
 use crossterm::event::{KeyEvent, KeyEventKind, KeyCode};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+use crate::TetrisError;
 
 /// En abstraction av alla olika input som jag kan få
-/// 
-/// Varje typ är en agering som jag kan ta i spelet. Jag kan rotera, flytta mig i många olika håll, jag kan snabbfalla och jag kan avsluta spelet
+///
+/// Varje typ är en agering som jag kan ta i spelet. Jag kan rotera (medsols, motsols eller ett halvt varv), flytta mig i många olika håll, jag kan snabbfalla och jag kan avsluta spelet
+/// Hold lägger undan biten till senare och Pause pausar spelet. Export sparar spelplanen som en fumen. Resize är inte en tangent, utan att terminalen har bytt storlek till (bredd, höjd).
+#[derive(Clone, Copy, PartialEq)]
 pub enum InputEvent {
-    Rotate,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
     Down,
     Right,
     Left,
     Drop,
+    Hold,
+    Pause,
+    Export,
     Quit,
+    Resize(u16, u16),
 }
 
-/// Denna funktionen hanterar interaktionen mellan tangenttryck och event i spelet. Den konverterar alltså knapptryck till en rörelse/händelse i spelet.
-/// 
-/// Return: Option<InputEvent> - antingen en None, eller en Some(InputEvent) som beskriver ett spelares input
-/// 
-/// Exempel:
-///     input() -> None;
-///     input() -> Some(InputEvent::Drop);
-///     input() -> Some(InputEvent::Left);
-///     input() -> Some(InputEvent::Right);
-///     input() -> Some(InputEvent::Down);
-///     input() -> Some(InputEvent::Rotate);
-pub fn input() -> Option<InputEvent> {
-    enable_raw_mode().unwrap();
-    let event = if poll(Duration::ZERO).unwrap() {
-        match read().unwrap() {
-            Event::Key(KeyEvent{kind: KeyEventKind::Press, code, ..}) => match code {
-                KeyCode::Char(' ') | KeyCode::Backspace => Some(InputEvent::Drop),
-                KeyCode::Char('a') | KeyCode::Left => Some(InputEvent::Left),
-                KeyCode::Char('s') | KeyCode::Down => Some(InputEvent::Down),
-                KeyCode::Char('d') | KeyCode::Right => Some(InputEvent::Right),
-                KeyCode::Char('r') | KeyCode::Char('w') | KeyCode::Up => Some(InputEvent::Rotate),
-                KeyCode::Char('q') | KeyCode::Esc => Some(InputEvent::Quit),
-                _ => None,
-            },
+impl InputEvent {
+    /// Denna funktion ger tillbaka namnet på handlingen så som det visas i kontroll menyn.
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputEvent::RotateCw => "Rotate right",
+            InputEvent::RotateCcw => "Rotate left",
+            InputEvent::Rotate180 => "Rotate 180",
+            InputEvent::Down => "Soft drop",
+            InputEvent::Right => "Move right",
+            InputEvent::Left => "Move left",
+            InputEvent::Drop => "Hard drop",
+            InputEvent::Hold => "Hold",
+            InputEvent::Pause => "Pause",
+            InputEvent::Export => "Export fumen",
+            InputEvent::Quit => "Quit",
+            InputEvent::Resize(_, _) => "Resize",
+        }
+    }
+}
+
+/// Denna tabell kopplar ihop tangenter med handlingar i spelet. Ordningen bestämmer även i vilken ordning kontrollerna visas i menyn.
+const BINDINGS: [(KeyCode, InputEvent); 19] = [
+    (KeyCode::Char('a'), InputEvent::Left),
+    (KeyCode::Left, InputEvent::Left),
+    (KeyCode::Char('d'), InputEvent::Right),
+    (KeyCode::Right, InputEvent::Right),
+    (KeyCode::Char('r'), InputEvent::RotateCw),
+    (KeyCode::Char('w'), InputEvent::RotateCw),
+    (KeyCode::Char('x'), InputEvent::RotateCw),
+    (KeyCode::Up, InputEvent::RotateCw),
+    (KeyCode::Char('z'), InputEvent::RotateCcw),
+    (KeyCode::Char('v'), InputEvent::Rotate180),
+    (KeyCode::Char('s'), InputEvent::Down),
+    (KeyCode::Down, InputEvent::Down),
+    (KeyCode::Char(' '), InputEvent::Drop),
+    (KeyCode::Backspace, InputEvent::Drop),
+    (KeyCode::Char('c'), InputEvent::Hold),
+    (KeyCode::Char('p'), InputEvent::Pause),
+    (KeyCode::Char('f'), InputEvent::Export),
+    (KeyCode::Char('q'), InputEvent::Quit),
+    (KeyCode::Esc, InputEvent::Quit),
+];
+
+/// En abstraction av input i menyn
+///
+/// Menyn navigeras med piltangenterna, Enter väljer och Esc går tillbaka. Char och Erase används när man skriver in text, t.ex. sitt namn. Resize betyder att terminalen har bytt storlek.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuEvent {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+    Erase,
+    Char(char),
+    Resize(u16, u16),
+}
+
+/// Denna struct är kopplingen till tangentbordet
+///
+/// Terminalen måste vara i raw mode (se TerminalSession) för att varje knapptryck ska komma direkt. En egen tråd läser alla event från terminalen, tidsstämplar dem och skickar dem över en kanal. På så sätt tappas inga knapptryck, även om många kommer samtidigt.
+pub struct Input {
+    receiver: Receiver<(Instant, Event)>,
+}
+
+impl Input {
+    /// Denna funktion startar tråden som läser input.
+    ///
+    /// Exempel:
+    ///     Input::start() -> Input
+    pub fn start() -> Self {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            // tråden avslutas vid nästa event efter att Input har släppts och kanalen stängts
+            while let Ok(event) = read() {
+                if sender.send((Instant::now(), event)).is_err() {
+                    break;
+                }
+            }
+        });
+        Input { receiver }
+    }
+
+    /// Denna funktionen hanterar interaktionen mellan tangenttryck och event i spelet. Den konverterar alltså knapptryck till en rörelse/händelse i spelet.
+    ///
+    /// Den väntar som längst timeout på att något ska hända. Under tiden sover programmet istället för att snurra runt i spel loopen, så att datorn inte jobbar i onödan. När något har hänt ges alla event som väntar tillbaka på en gång.
+    ///
+    /// Argument 1(self): &Input - en reference till input
+    /// Argument 2(timeout): Duration - hur länge den längst väntar på input
+    ///
+    /// Return: Result<Vec<(Instant, InputEvent)>, TetrisError> - alla spelarens input i ordning, med tiden de kom. Tom om inget hände. Ett fel om input tråden har slutat, annars skulle spel loopen aldrig sova igen
+    ///
+    /// Exempel:
+    ///     input.events(Duration::from_millis(16)) -> Ok([])
+    ///     input.events(Duration::from_millis(16)) -> Ok([(Instant, InputEvent::Left), (Instant, InputEvent::Drop)])
+    ///     input.events(Duration::from_millis(16)) -> Ok([(Instant, InputEvent::Resize(120, 50))])
+    pub fn events(&self, timeout: Duration) -> Result<Vec<(Instant, InputEvent)>, TetrisError> {
+        let mut events = Vec::new();
+        match self.receiver.recv_timeout(timeout) {
+            Ok(first) => {
+                events.push(first);
+                events.extend(self.receiver.try_iter());
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+        }
+        Ok(events
+            .into_iter()
+            .filter_map(|(time, event)| Some((time, game_event(event)?)))
+            .collect())
+    }
+
+    /// Denna funktion väntar på nästa tangenttryck i menyn och konverterar det till ett MenuEvent. Till skillnad från events() blockerar den tills något händer, menyn har ju inget annat att göra under tiden.
+    ///
+    /// Return: Result<MenuEvent, TetrisError> - vad användaren vill göra i menyn, eller ett fel om terminalen inte går att läsa längre
+    ///
+    /// Exempel:
+    ///     input.menu_event() -> Ok(MenuEvent::Down);
+    ///     input.menu_event() -> Ok(MenuEvent::Select);
+    ///     input.menu_event() -> Ok(MenuEvent::Char('c'));
+    pub fn menu_event(&self) -> Result<MenuEvent, TetrisError> {
+        loop {
+            let (_, event) = self
+                .receiver
+                .recv()
+                .map_err(|_| stopped())?;
+            if let Some(event) = menu_event(event) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+/// Felet som ges när input tråden har slutat, t.ex. för att terminalen inte gick att läsa.
+fn stopped() -> TetrisError {
+    TetrisError::Terminal(io::Error::other("the input thread stopped"))
+}
+
+/// Denna funktion konverterar ett event från terminalen till en handling i spelet, eller None om eventet inte betyder något i spelet.
+fn game_event(event: Event) -> Option<InputEvent> {
+    match event {
+        Event::Key(KeyEvent{kind: KeyEventKind::Press, code, ..}) => BINDINGS
+            .iter()
+            .find(|(key, _)| *key == code)
+            .map(|(_, event)| *event),
+        Event::Resize(width, height) => Some(InputEvent::Resize(width, height)),
+        _ => None,
+    }
+}
+
+/// Denna funktion konverterar ett event från terminalen till ett MenuEvent, eller None om eventet inte används i menyn.
+fn menu_event(event: Event) -> Option<MenuEvent> {
+    match event {
+        Event::Key(KeyEvent{kind: KeyEventKind::Press, code, ..}) => match code {
+            KeyCode::Up => Some(MenuEvent::Up),
+            KeyCode::Down => Some(MenuEvent::Down),
+            KeyCode::Left => Some(MenuEvent::Left),
+            KeyCode::Right => Some(MenuEvent::Right),
+            KeyCode::Enter => Some(MenuEvent::Select),
+            KeyCode::Esc => Some(MenuEvent::Back),
+            KeyCode::Backspace => Some(MenuEvent::Erase),
+            KeyCode::Char(char) => Some(MenuEvent::Char(char)),
             _ => None,
+        },
+        Event::Resize(width, height) => Some(MenuEvent::Resize(width, height)),
+        _ => None,
+    }
+}
+
+/// Denna funktion samlar ihop alla tangenter som hör till varje handling, så att kontrollerna kan visas i menyn.
+///
+/// Return: Vec<(&'static str, String)> - handlingens namn och tangenterna som utför den
+///
+/// Exempel:
+///     controls() -> [("Move left", "a / Left"), ("Move right", "d / Right"), ...]
+pub fn controls() -> Vec<(&'static str, String)> {
+    let mut controls: Vec<(InputEvent, Vec<String>)> = Vec::new();
+    for (key, event) in BINDINGS {
+        match controls.iter_mut().find(|(existing, _)| *existing == event) {
+            Some((_, keys)) => keys.push(key_name(key)),
+            None => controls.push((event, vec![key_name(key)])),
         }
-    } else {
-        None
-    };
-    disable_raw_mode().unwrap();
-    event
+    }
+    controls.into_iter().map(|(event, keys)| (event.name(), keys.join(" / "))).collect()
+}
+
+/// Denna funktion ger ett läsbart namn till en tangent.
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(char) => char.to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        _ => "?".to_string(),
+    }
 }
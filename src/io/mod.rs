@@ -1,3 +1,7 @@
 /// denna modul importerar andra moduler. Moduler är stycken kod som kan importeras och återanvändas.
+pub mod animation;
 pub mod input;
 pub mod output;
+pub mod renderer;
+pub mod session;
+pub mod theme;
@@ -1,83 +1,191 @@
-use std::io::stdout;
-
-use crossterm::{execute, terminal, Result};
-use crossterm::cursor::{Hide, MoveTo};
-use crossterm::terminal::{Clear, ClearType};
-use crossterm::style::{SetBackgroundColor, ResetColor, Color, SetForegroundColor};
+use crossterm::style::Color;
 
 use crate::*;
 
-pub const COLORS: [Color;7] = [Color::Black, Color::Blue, Color::Cyan, Color::Green, Color::Magenta, Color::Red, Color::Yellow];
-const BLOCK_WIDTH: usize = 4;
-const BLOCK_HEIGHT: usize = 2;
-const BACKGROUND: &'static str = include_str!("background.txt");
-
-/// Detta är en representation av kommandon som skickas till terminalen
-/// 
-/// Varje typ av denna enum representerar ett execute!() kommand. och typerna i dessa (String, usize...) är anpassade fär att vara lättanvände i mitt syfte.
-enum Instruction {
-    MoveTo(usize, usize),
-    Color(Color),
-    TextColor(Color),
-    Print(String),
+const BACKGROUND: &str = include_str!("background.txt");
+/// Tecknet som används i HalfBlock läget. Förgrunden är den övre cellen och bakgrunden den undre.
+const HALF_BLOCK: char = '▀';
+/// Samma som HALF_BLOCK fast för när bara den undre cellen är fylld.
+const LOWER_HALF_BLOCK: char = '▄';
+/// Sidopanelens storlek i tecken, den ligger till höger om spelplanen med PANEL_GAP tecken mellanrum efter ramen.
+/// Panelen är så hög att hold och nästa bit får plats även för bitar som är PREVIEW_ROWS rader höga.
+const PANEL_WIDTH: usize = 12;
+const PANEL_HEIGHT: usize = 7 + PREVIEW_ROWS;
+const PANEL_GAP: usize = 1;
+/// Den högsta bit som får plats i hold och nästa, i rader av spelplanen. Högre bitar klipps nertill.
+const PREVIEW_ROWS: usize = 6;
+/// Färgen i temat som skräp block ritas med, den är grå i classic temat.
+const GARBAGE_COLOR: usize = 7;
+
+/// Hur stor varje cell på spelplanen ritas i terminalen
+///
+/// Ju mindre terminalen är desto mindre celler används, så att spelet går att spela även i små fönster (t.ex. en tmux ruta).
+///     Large: 4x2 tecken per cell
+///     Small: 2x1 tecken per cell
+///     HalfBlock: 1 tecken per cell, där varje rad i terminalen visar två rader av spelplanen med tecknet ▀
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellSize {
+    Large,
+    Small,
+    HalfBlock,
 }
 
-impl Instruction {
-    /// Denna funktion utför en instruktion och ger tillbaka (returnar) med antingen ett Ok() som betyder att den lyckades eller ett std::io::Error med ett meddelande om varför det inte fungerade.
-    fn perform(&self) -> Result<()> {
+impl CellSize {
+    /// Alla storlekar, från störst till minst. Den första som får plats används.
+    pub const ALL: [CellSize; 3] = [CellSize::Large, CellSize::Small, CellSize::HalfBlock];
+
+    /// Denna funktion ger hur många tecken breda och höga en cell är. (bredd, höjd)
+    fn block(&self) -> (usize, usize) {
         match self {
-            Instruction::MoveTo(x, y) => execute!(stdout(), MoveTo(*x as u16, *y as u16)),
-            Instruction::Color(color) => execute!(stdout(), SetBackgroundColor(*color)),
-            Instruction::TextColor(color) => execute!(stdout(), SetForegroundColor(*color)),
-            Instruction::Print(text) => {
-                print!("{:.BLOCK_WIDTH$}", text);
-                Ok(())
-            }
+            CellSize::Large => (4, 2),
+            CellSize::Small => (2, 1),
+            CellSize::HalfBlock => (1, 1),
+        }
+    }
+
+    /// Denna funktion ger hur många rader av spelplanen som får plats i ett tecken på höjden.
+    fn rows_per_line(&self) -> usize {
+        match self {
+            CellSize::HalfBlock => 2,
+            _ => 1,
         }
     }
+
+    /// Denna funktion ger storleken på spelplanen i tecken, utan ramen. (bredd, höjd)
+    ///
+    /// Exempel:
+    ///     CellSize::Large.board_size() -> (64, 40)
+    ///     CellSize::HalfBlock.board_size() -> (16, 10)
+    pub fn board_size(&self) -> (usize, usize) {
+        let (width, height) = self.block();
+        (COLUMNS * width, ROWS.div_ceil(self.rows_per_line()) * height)
+    }
+
+    /// Denna funktion ger den minsta storleken på terminalen som spelplanen, ramen och sidopanelen får plats i. (bredd, höjd)
+    pub fn required_size(&self) -> (u16, u16) {
+        let (width, height) = self.board_size();
+        ((width + 2 + PANEL_GAP + PANEL_WIDTH) as u16, (height + 1).max(PANEL_HEIGHT) as u16)
+    }
+
+    /// Denna funktion väljer den största cell storleken som får plats i terminalen.
+    ///
+    /// Argument 1(width): u16 - terminalens bredd
+    /// Argument 2(height): u16 - terminalens höjd
+    ///
+    /// Return: Option<CellSize> - storleken, eller None om inte ens den minsta får plats
+    ///
+    /// Exempel:
+    ///     CellSize::fitting(80, 45) -> Some(CellSize::Large)
+    ///     CellSize::fitting(50, 24) -> Some(CellSize::Small)
+    ///     CellSize::fitting(10, 5) -> None
+    pub fn fitting(width: u16, height: u16) -> Option<CellSize> {
+        Self::ALL.into_iter().find(|cell_size| {
+            let (required_width, required_height) = cell_size.required_size();
+            width >= required_width && height >= required_height
+        })
+    }
 }
 
 /// Denna struct samlar data för dem visuella delarna och hanterar interaktionen med terminalen
-/// 
+///
 /// Den har även passande hjälpfunktioner för att manipulera denna data. Detta är mesta dels för att optimisera spelet.
-/// 
+///
 /// Att göra många execute!() calls är dyrt för prestanda. därför håller vi endast koll på förändringar, alltså vi spara hur spelet ser ut och ser vilka block som har förändrats. Sedan målar vi endast dom blocken. Detta är varför vi behöver data i denna struct, för att se förändringar.
-pub struct Output {
-    grid: [[usize;COLUMNS];ROWS],
-    background: [[char;BLOCK_WIDTH * COLUMNS];BLOCK_HEIGHT * ROWS],
-    foreground: [[char;BLOCK_WIDTH * COLUMNS];BLOCK_HEIGHT * ROWS],
+///
+/// Själva ritandet görs av en Renderer. Som standard är det terminalen, men output kan även rita till t.ex. en Buffer i minnet.
+///
+/// Bakgrunden är ett text lager lika stort som spelplanen i tecken. Dess storlek beror alltså på vilken CellSize som används.
+///
+/// Ovanpå spelplanen ligger ett overlay lager med tillfälliga meddelanden, t.ex. "TETRIS!" eller "Paused". Meddelandena centreras på spelplanen och tas bort när deras tid har gått ut.
+///
+/// Animationer följer spelets klocka istället för verklig tid. tick() ger output den nuvarande tiden och animationer som är klara tas bort.
+///
+/// Bredvid spelplanen finns en sidopanel med poäng, nivå, rader, tid, hold och nästa bit. panel sparar vad som senast ritades där, så att bara rader som ändrats målas om.
+///
+/// Cellerna på spelplanen vet bara vilken form de kom från. kinds är vilken färg i temat varje form ritas med, i samma ordning som spelets former.
+pub struct Output<R: Renderer = Terminal> {
+    renderer: R,
+    grid: [[Option<Cell>;COLUMNS];ROWS],
+    cell_size: CellSize,
+    background_text: String,
+    background: Vec<Vec<char>>,
+    messages: Vec<Message>,
+    overlay: Vec<Vec<Option<char>>>,
+    panel: Vec<Vec<PanelCell>>,
+    animations: Vec<(Animation, Duration, Duration)>,
+    animation_speed: AnimationSpeed,
+    now: Duration,
     offset: (usize, usize),
+    size: (u16, u16),
+    theme: Theme,
+    kinds: Vec<usize>,
+    border_changed: bool,
 }
 
 impl Output {
-    /// Denna funktion skapar ett nytt output instans med grund värden
-    pub fn new() -> Self {
-        let (width, _height) = terminal::size().unwrap();
-        let offset = Self::offset(width);
-        execute!(stdout(), Hide, MoveTo(0, 0), Clear(ClearType::FromCursorDown)).unwrap();
-        Output {
-            grid: [[0;COLUMNS];ROWS],
-            background: Self::parse_background(BACKGROUND),
-            foreground: [[' ';BLOCK_WIDTH * COLUMNS];BLOCK_HEIGHT * ROWS],
-            offset,
+    /// Denna funktion skapar ett nytt output instans med grund värden som ritar till terminalen
+    ///
+    /// Return: Result<Output, TetrisError> - output, eller ett fel om terminalens storlek inte gick att läsa
+    pub fn new() -> Result<Self, TetrisError> {
+        Self::with_renderer(Terminal::new()?)
+    }
+}
+
+impl<R: Renderer> Output<R> {
+    /// Denna funktion skapar ett nytt output instans med grund värden som ritar med en valfri renderer
+    ///
+    /// Argument 1(renderer): R - det som output ska rita till
+    ///
+    /// Return: Result<Output<R>, TetrisError> - output, eller ett fel om renderern inte gick att läsa av eller rita till
+    ///
+    /// Exempel:
+    ///     Output::with_renderer(Buffer::new(80, 45)) -> Ok(Output<Buffer>)
+    pub fn with_renderer(renderer: R) -> Result<Self, TetrisError> {
+        let size = renderer.size()?;
+        let cell_size = CellSize::fitting(size.0, size.1).unwrap_or(CellSize::HalfBlock);
+        let mut output = Output {
+            renderer,
+            grid: [[None;COLUMNS];ROWS],
+            cell_size,
+            background_text: BACKGROUND.to_string(),
+            background: Self::parse_background(BACKGROUND, cell_size),
+            messages: Vec::new(),
+            overlay: Self::parse_overlay(&[], cell_size),
+            panel: Vec::new(),
+            animations: Vec::new(),
+            animation_speed: AnimationSpeed::Normal,
+            now: Duration::ZERO,
+            offset: Self::offset(size.0, cell_size),
+            size,
+            theme: Theme::default(),
+            kinds: Vec::new(),
+            border_changed: true,
+        };
+        if !output.fits() {
+            output.resize(size.0, size.1)?;
         }
+        Ok(output)
     }
 
     /// Denna funktion läser filen background.txt och formatterar denna för att se fin ut. D.V.S. Vi centrerar texten och delar upp den i block för att vara lättare att jobba med.
-    fn parse_background(background_str: &'static str) -> [[char;BLOCK_WIDTH * COLUMNS];BLOCK_HEIGHT * ROWS] {
-        let mut background = [[' ';BLOCK_WIDTH * COLUMNS];BLOCK_HEIGHT * ROWS];
+    ///
+    /// Text som inte får plats på spelplanen klipps bort.
+    fn parse_background(background_str: &str, cell_size: CellSize) -> Vec<Vec<char>> {
+        let (board_width, board_height) = cell_size.board_size();
+        let mut background = vec![vec![' '; board_width]; board_height];
         let mut widths = Vec::new();
         let mut height = 0;
         for line in background_str.lines() {
-            widths.push(line.len());
+            widths.push(line.chars().count());
             height += 1;
         }
 
         for (y, line) in background_str.lines().enumerate() {
             for (x, char) in line.chars().enumerate() {
                 let width = widths[y];
-                let x = x + (COLUMNS * BLOCK_WIDTH - width) / 2;
-                let y = y + (ROWS * BLOCK_HEIGHT - height) / 2;
+                let x = x + board_width.saturating_sub(width) / 2;
+                let y = y + board_height.saturating_sub(height) / 2;
+                if x >= board_width || y >= board_height { continue; }
                 background[y][x] = char;
             }
         }
@@ -85,124 +193,631 @@ impl Output {
         background
     }
 
-    /// Denna funktion jämför grid hos output och gamestate och hittar vilka block positioner som har ändrade värden.
-    fn changes(&mut self, data: &GameState) -> Vec<(usize, usize)> {
-        let next_grid = Self::next_grid(data);
+    /// Denna funktion bygger overlay lagret från meddelandena. Varje meddelande får en egen rad med ett mellanrum på var sida, och alla rader centreras tillsammans på spelplanen.
+    ///
+    /// Positioner som inte täcks av något meddelande är None, där syns spelplanen igenom.
+    fn parse_overlay(messages: &[Message], cell_size: CellSize) -> Vec<Vec<Option<char>>> {
+        let (board_width, board_height) = cell_size.board_size();
+        let mut overlay = vec![vec![None; board_width]; board_height];
+        let top = board_height.saturating_sub(messages.len()) / 2;
+        for (y, message) in messages.iter().enumerate() {
+            let text: Vec<char> = format!(" {} ", message.text).chars().collect();
+            let left = board_width.saturating_sub(text.len()) / 2;
+            for (x, char) in text.into_iter().enumerate() {
+                let (x, y) = (x + left, y + top);
+                if x >= board_width || y >= board_height { continue; }
+                overlay[y][x] = Some(char);
+            }
+        }
+        overlay
+    }
+
+    /// Denna funktion tar bort meddelanden vars tid har gått ut och bygger om overlay lagret. Cellerna där overlay lagret ändrats markeras så att de målas om, på så sätt kommer spelplanen under ett meddelande tillbaka när det försvinner.
+    fn update_overlay(&mut self) {
+        let now = Instant::now();
+        self.messages.retain(|message| message.expires.is_none_or(|expires| expires > now));
+        let overlay = Self::parse_overlay(&self.messages, self.cell_size);
+        let (block_width, block_height) = self.cell_size.block();
+        let rows_per_line = self.cell_size.rows_per_line();
+        for (line, (old, new)) in self.overlay.iter().zip(&overlay).enumerate() {
+            for (x, _) in old.iter().zip(new).enumerate().filter(|(_, (old, new))| old != new) {
+                let row = line / block_height * rows_per_line;
+                for row in row..(row + rows_per_line).min(ROWS) {
+                    self.grid[row][x / block_width] = None;
+                }
+            }
+        }
+        self.overlay = overlay;
+    }
+
+    /// Denna funktion visar ett meddelande mitt på spelplanen. Om samma meddelande redan visas får det istället en ny livstid.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(text): &str - meddelandet
+    /// Argument 3(lifetime): Option<Duration> - hur länge meddelandet visas, None betyder tills det tas bort med hide_message()
+    ///
+    /// Exempel:
+    ///     output.show_message("TETRIS!", Some(Duration::from_millis(1500)));
+    ///     output.show_message("Paused", None);
+    pub fn show_message(&mut self, text: &str, lifetime: Option<Duration>) {
+        let expires = lifetime.map(|lifetime| Instant::now() + lifetime);
+        match self.messages.iter_mut().find(|message| message.text == text) {
+            Some(message) => message.expires = expires,
+            None => self.messages.push(Message { text: text.to_string(), expires }),
+        }
+        self.update_overlay();
+    }
+
+    /// Denna funktion tar bort ett meddelande innan dess tid har gått ut.
+    ///
+    /// Exempel:
+    ///     output.hide_message("Paused");
+    pub fn hide_message(&mut self, text: &str) {
+        self.messages.retain(|message| message.text != text);
+        self.update_overlay();
+    }
+
+    /// Denna funktion bestämmer hur snabbt animationer spelas, eller om de inte spelas alls.
+    pub fn set_animation_speed(&mut self, speed: AnimationSpeed) {
+        self.animation_speed = speed;
+    }
+
+    /// Denna funktion startar en animation på spelplanen.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(animation): Animation - animationen som ska spelas
+    ///
+    /// Return: Duration - hur länge animationen varar, noll om animationer är avstängda
+    ///
+    /// Exempel:
+    ///     output.animate(Animation::Clear(vec![18, 19])) -> 400ms
+    ///     output.animate(Animation::Lock(vec![(3, 19)])) -> 0ns
+    pub fn animate(&mut self, animation: Animation) -> Duration {
+        match self.animation_speed.scale(animation.length()) {
+            Some(length) => self.animate_for(animation, length),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Denna funktion startar en animation som ska vara exakt length lång, oavsett animations hastighet. Den används när spelet bestämmer hur länge något pågår, t.ex. när rensade rader ligger kvar.
+    ///
+    /// Return: Duration - hur länge animationen varar, noll om animationer är avstängda
+    ///
+    /// Exempel:
+    ///     output.animate_for(Animation::Clear(vec![23]), Duration::from_millis(700)) -> 700ms
+    pub fn animate_for(&mut self, animation: Animation, length: Duration) -> Duration {
+        if self.animation_speed == AnimationSpeed::Off || length.is_zero() {
+            return Duration::ZERO;
+        }
+        self.animations.push((animation, self.now, length));
+        length
+    }
+
+    /// Denna funktion ger hur länge det är kvar tills något på skärmen ändras av sig självt, alltså utan att spelet har ändrats. Spel loopen kan sova så länge.
+    ///
+    /// Return: Option<Duration> - tiden kvar, noll om en animation pågår och None om inget väntar
+    ///
+    /// Exempel:
+    ///     output.next_update() -> Some(0ns)
+    ///     output.next_update() -> Some(1.2s)
+    ///     output.next_update() -> None
+    pub fn next_update(&self) -> Option<Duration> {
+        if !self.animations.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        self.next_message()
+    }
+
+    /// Denna funktion ger hur länge det är kvar tills nästa meddelande försvinner. Animationer räknas inte, de står still medan spelets klocka står still.
+    /// Meddelanden som redan har gått ut räknas inte heller, de tas bort nästa gång något ritas.
+    ///
+    /// Return: Option<Duration> - tiden kvar, None om inget meddelande ska försvinna
+    ///
+    /// Exempel:
+    ///     output.next_message() -> Some(1.2s)
+    ///     output.next_message() -> None
+    pub fn next_message(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.messages
+            .iter()
+            .filter_map(|message| message.expires)
+            .filter(|&expires| expires > now)
+            .map(|expires| expires - now)
+            .min()
+    }
+
+    /// Denna funktion ger output spelets nuvarande tid. Animationer som är klara tas bort, cellerna de ritade på målas då om nästa gång något ritas.
+    ///
+    /// Exempel:
+    ///     output.tick(clock.now());
+    pub fn tick(&mut self, now: Duration) {
+        self.now = now;
+        self.animations.retain(|(_, start, length)| *start + *length > now);
+    }
+
+    /// Denna funktion jämför output grid med nästa grid och hittar vilka block positioner som har ändrade värden.
+    fn changes(&mut self, next_grid: [[Cell;COLUMNS];ROWS]) -> Vec<(usize, usize)> {
         let mut changed_blocks = Vec::new();
-        for x in 0..COLUMNS {
-            for y in 0..ROWS {
-                if self.grid[y][x] != next_grid[y][x] {
+        for (y, row) in next_grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if self.grid[y][x] != Some(*cell) {
                     changed_blocks.push((x, y));
+                    self.grid[y][x] = Some(*cell);
                 }
             }
         }
-        self.grid = next_grid;
         changed_blocks
     }
 
     /// Denna funktion tar data över grid och spelare för att bestämma vilka värden output grid skal ha. Spelaren syns inte om man inte gör detta då den inte är en del av gamestatets grid förräns den placerats.
-    fn next_grid(data: &GameState) -> [[usize;COLUMNS];ROWS] {
-        let mut grid = data.grid;
+    ///
+    /// Animationer som pågår ritas ovanpå i den ordning de startade. Allt ritas på hela spelplanen, sedan tas de gömda raderna överst bort.
+    fn next_grid(&self, data: &GameState) -> [[Cell;COLUMNS];ROWS] {
+        let mut board = data.grid;
         if let Some(player) = &data.player {
             for (x, y) in player.extent() {
                 let x = x as usize;
                 let y = y as usize;
-                if x < COLUMNS && y < ROWS {
-                    grid[y][x] = player.color;
+                if x < COLUMNS && y < BOARD_ROWS {
+                    board[y][x] = Cell::Piece(data.current);
                 }
             }
         }
+        for (animation, start, length) in &self.animations {
+            let progress = self.now.saturating_sub(*start).as_secs_f32() / length.as_secs_f32();
+            animation.apply(&mut board, progress.min(1.0));
+        }
+        let mut grid = [[Cell::Empty;COLUMNS];ROWS];
+        grid.copy_from_slice(&board[HIDDEN_ROWS..]);
         grid
     }
 
-    /// Denna funktion tar ett x och y värde och bestämmer vilken färg denna cell skal ha. Denna är ansvarig för rutnätet som bakgrunden har.
+    /// Denna funktion tar ett x och y värde och bestämmer vilken färg denna cell skal ha. Denna är ansvarig för rutnätet som bakgrunden har. Fyllda celler får färgen som temat ger formen de kom från.
     fn color_at(&self, x: usize, y: usize) -> Color {
-        let value = self.grid[y][x];
-        if value == 0 {
-            if (x + y) % 2 == 0 {
-                Color::Rgb { r: 15, g: 15, b: 15 }
-            } else {
-                Color::Rgb { r: 10, g: 10, b: 10 }
-            }
-        } else {
-            COLORS[value]
+        match self.grid[y][x] {
+            Some(Cell::Piece(index)) => self.theme.piece(self.kinds.get(index).copied().unwrap_or_default()),
+            Some(Cell::Garbage) => self.theme.piece(GARBAGE_COLOR),
+            Some(Cell::Flash) => self.theme.text,
+            _ if (x + y).is_multiple_of(2) => self.theme.empty.0,
+            _ => self.theme.empty.1,
+        }
+    }
+
+    /// Denna funktion skapar instruktioner för ramen runt spelplanen. Ramen är en kolumn på var sida och en rad under planen.
+    fn border(&self) -> Vec<Instruction> {
+        let (board_width, board_height) = self.cell_size.board_size();
+        let mut instructions = vec![Instruction::Color(self.theme.border)];
+        for y in 0..board_height {
+            instructions.push(Instruction::MoveTo(self.offset.0 - 1, y + self.offset.1));
+            instructions.push(Instruction::Print(" ".to_string()));
+            instructions.push(Instruction::MoveTo(self.offset.0 + board_width, y + self.offset.1));
+            instructions.push(Instruction::Print(" ".to_string()));
+        }
+        instructions.push(Instruction::MoveTo(self.offset.0 - 1, board_height + self.offset.1));
+        instructions.push(Instruction::Print(" ".repeat(board_width + 2)));
+        instructions
+    }
+
+    /// Denna funktion byter tema. Eftersom alla färger ändras målas allting om nästa gång något ritas.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(theme): Theme - det nya temat
+    pub fn set_theme(&mut self, theme: Theme) {
+        if self.theme != theme {
+            self.theme = theme;
+            self.grid = [[None;COLUMNS];ROWS];
+            self.panel.clear();
+            self.border_changed = true;
         }
     }
 
     /// Denna funktionen tar en vector över alla celler som ändrats konverterar detta till instruktioner som terminalen skal utföra.
+    ///
+    /// Cellerna målas rad för rad i terminalen, från vänster till höger. Då hamnar celler som ligger bredvid varandra efter varandra i listan, så att Instruction::optimize kan slå ihop dem till en enda utskrift.
     fn instructions(&self, changes: Vec<(usize, usize)>) -> Vec<Instruction> {
+        if self.cell_size == CellSize::HalfBlock {
+            return self.half_block_instructions(changes);
+        }
+        let (block_width, block_height) = self.cell_size.block();
         let mut instructions = Vec::new();
-        instructions.push(Instruction::TextColor(Color::Grey));
-        for (x, y) in changes {
-            instructions.push(Instruction::Color(self.color_at(x, y)));
-            for row in 0..BLOCK_HEIGHT {
-                instructions.push(Instruction::MoveTo(x * BLOCK_WIDTH + self.offset.0, y * BLOCK_HEIGHT + row + self.offset.1));
-                if self.grid[y][x] == 0 {
-                    instructions.push(Instruction::Print(Self::compose_back_and_fore(&self.background[y * BLOCK_HEIGHT + row][(x * BLOCK_WIDTH)..(x * BLOCK_WIDTH + BLOCK_WIDTH)], &self.foreground[y * BLOCK_HEIGHT + row][(x * BLOCK_WIDTH)..(x * BLOCK_WIDTH + BLOCK_WIDTH)]).iter().collect()));
-                } else {
-                    instructions.push(Instruction::Print(self.foreground[y * BLOCK_HEIGHT + row][x * BLOCK_WIDTH..(x * BLOCK_WIDTH + BLOCK_WIDTH)].iter().collect()));
+        instructions.push(Instruction::TextColor(self.theme.text));
+        for row_changes in changes.chunk_by(|a, b| a.1 == b.1) {
+            let y = row_changes[0].1;
+            for row in 0..block_height {
+                let line = y * block_height + row;
+                for &(x, _) in row_changes {
+                    instructions.push(Instruction::MoveTo(x * block_width + self.offset.0, line + self.offset.1));
+                    for column in (x * block_width)..(x * block_width + block_width) {
+                        if let Some(char) = self.overlay[line][column] {
+                            instructions.push(Instruction::Color(self.theme.border));
+                            instructions.push(Instruction::Print(char.to_string()));
+                        } else if self.grid[y][x] == Some(Cell::Empty) {
+                            instructions.push(Instruction::Color(self.color_at(x, y)));
+                            instructions.push(Instruction::Print(self.background[line][column].to_string()));
+                        } else {
+                            instructions.push(Instruction::Color(self.color_at(x, y)));
+                            instructions.push(Instruction::Print(" ".to_string()));
+                        }
+                    }
                 }
             }
         }
         instructions
     }
 
-    /// Denna funktion kombinderar bakgrund och ui. Den ser till att UI alltid ligger över bakgrunder.
-    fn compose_back_and_fore(background: &[char], foreground: &[char]) -> [char;BLOCK_WIDTH] {
-        let mut composed = [' ';BLOCK_WIDTH];
-        for i in 0..BLOCK_WIDTH {
-            if foreground[i] == ' ' {
-                composed[i] = background[i]
+    /// Denna funktion gör samma sak som instructions() fast för HalfBlock läget. Där delar två rader av spelplanen på samma tecken, så om en av dem ändras måste tecknet målas om med båda färgerna.
+    ///
+    /// Tecken med text målas istället med textfärgen ovanpå den övre cellens färg. Meddelanden i overlay lagret målas ovanpå allt annat.
+    fn half_block_instructions(&self, changes: Vec<(usize, usize)>) -> Vec<Instruction> {
+        let mut positions: Vec<(usize, usize)> = changes.into_iter().map(|(x, y)| (x, y / 2)).collect();
+        positions.sort_by_key(|&(x, line)| (line, x));
+        positions.dedup();
+
+        let mut instructions = Vec::new();
+        for (x, line) in positions {
+            let top = line * 2;
+            let bottom = top + 1;
+            let empty = self.grid[top][x] == Some(Cell::Empty) && (bottom >= ROWS || self.grid[bottom][x] == Some(Cell::Empty));
+            let text = if empty { self.background[line][x] } else { ' ' };
+            instructions.push(Instruction::MoveTo(x + self.offset.0, line + self.offset.1));
+            if let Some(char) = self.overlay[line][x] {
+                instructions.push(Instruction::Color(self.theme.border));
+                instructions.push(Instruction::TextColor(self.theme.text));
+                instructions.push(Instruction::Print(char.to_string()));
+                continue;
+            }
+            instructions.push(Instruction::Color(self.color_at(x, top)));
+            if text != ' ' {
+                instructions.push(Instruction::TextColor(self.theme.text));
+                instructions.push(Instruction::Print(text.to_string()));
+            } else if bottom < ROWS {
+                instructions.push(Instruction::Color(self.color_at(x, bottom)));
+                instructions.push(Instruction::TextColor(self.color_at(x, top)));
+                instructions.push(Instruction::Print(HALF_BLOCK.to_string()));
             } else {
-                composed[i] = foreground[i]
+                instructions.push(Instruction::Print(" ".to_string()));
             }
         }
-        composed
+        instructions
     }
 
-    /// Denna funktion manipulerar foreground fältet för att skriva text med spelarens poäng. Den beräknar positionen i 2D arrayen som den skal ändra för att det skal bli korrekt.
-    pub fn draw_score(&mut self, score: String) {
-        for (x, char) in score.chars().enumerate() {
-            let x = (COLUMNS * BLOCK_WIDTH - score.len()) / 2 + x;
-            let y = 6;
-            self.foreground[y][x] = char;
-            self.grid[y / BLOCK_HEIGHT][x / BLOCK_WIDTH] = usize::MAX;
+    /// Denna funktion målar sidopanelen med information om spelet. Panelen byggs upp på nytt varje gång, men bara rader som skiljer sig från förra gången målas.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(data): &GameState - spelet som informationen hämtas från
+    /// Argument 3(time): Duration - hur länge spelet har pågått
+    ///
+    /// Exempel:
+    ///     output.draw_hud(&game, game_timer.elapsed())?;
+    pub fn draw_hud(&mut self, data: &GameState, time: Duration) -> Result<(), TetrisError> {
+        if !self.fits() { return Ok(()); }
+        let seconds = time.as_secs();
+        let pps = if time.is_zero() { 0.0 } else { data.pieces as f64 / time.as_secs_f64() };
+        let mut panel = vec![
+            Self::panel_text("Score", &format!("{:0>5}", data.points)),
+            Self::panel_text("Level", &data.level().to_string()),
+            Self::panel_text("Lines", &data.lines.to_string()),
+            Self::panel_text("Time", &format!("{}:{:0>2}", seconds / 60, seconds % 60)),
+            Self::panel_text("PPS", &format!("{pps:.2}")),
+            Self::panel_text("Hold", ""),
+        ];
+        // bitarna får lika mycket plats som den högsta biten i bituppsättningen behöver
+        let rows = data
+            .shapes()
+            .iter()
+            .map(|shape| {
+                let extent = shape.orientation(shape.spawn_rotation());
+                let min_y = extent.iter().map(|&(_, y)| y).min().unwrap_or(0);
+                let max_y = extent.iter().map(|&(_, y)| y).max().unwrap_or(0);
+                (max_y - min_y + 1) as usize
+            })
+            .max()
+            .unwrap_or(1)
+            .clamp(1, PREVIEW_ROWS);
+        panel.extend(self.panel_piece(data.held(), rows));
+        panel.push(Self::panel_text("Next", ""));
+        panel.extend(self.panel_piece(data.next(), rows));
+
+        let x = self.offset.0 + self.cell_size.board_size().0 + 1 + PANEL_GAP;
+        let mut instructions = Vec::new();
+        for (y, line) in panel.iter().enumerate() {
+            if self.panel.get(y) == Some(line) { continue; }
+            instructions.push(Instruction::MoveTo(x, y + self.offset.1));
+            for cell in line {
+                instructions.push(Instruction::Color(cell.background));
+                instructions.push(Instruction::TextColor(cell.foreground.unwrap_or(self.theme.text)));
+                instructions.push(Instruction::Print(cell.char.to_string()));
+            }
+        }
+        self.panel = panel;
+        if !instructions.is_empty() {
+            self.execute(instructions)?;
         }
+        Ok(())
+    }
+
+    /// Denna funktion skapar en rad i sidopanelen med namnet till vänster och värdet till höger.
+    ///
+    /// Exempel:
+    ///     Output::panel_text("Score", "00400") -> "Score  00400"
+    fn panel_text(label: &str, value: &str) -> Vec<PanelCell> {
+        let text = format!("{label:<width$}{value}", width = PANEL_WIDTH.saturating_sub(value.chars().count()));
+        text.chars()
+            .chain(std::iter::repeat(' '))
+            .take(PANEL_WIDTH)
+            .map(|char| PanelCell { char, foreground: None, background: Color::Reset })
+            .collect()
     }
-    
-    /// Denna funktion är samma som draw_score fast den sitter i nedre vänstra hörnet. Kan vara bra att ha en generel funktion eftersom att dessa funktioner är väldigt lika.
-    pub fn draw_fps(&mut self, fps: String) {
-        for (x, char) in fps.chars().enumerate() {
-            let x = x;
-            let y = ROWS * BLOCK_HEIGHT - 1;
-            self.foreground[y][x] = char;
-            self.grid[y / BLOCK_HEIGHT][x / BLOCK_WIDTH] = usize::MAX;
+
+    /// Denna funktion ritar en bit i sidopanelen, t.ex. nästa bit. Biten ritas med halva tecken så att två rader får plats på en rad i terminalen oavsett cell storlek.
+    ///
+    /// Argument 1(self): &self - output
+    /// Argument 2(shape): Option<&Shape> - biten, None ritar en tom ruta
+    /// Argument 3(rows): usize - hur många rader av spelplanen rutan är hög, den blir hälften så många rader i terminalen
+    fn panel_piece(&self, shape: Option<&Shape>, rows: usize) -> Vec<Vec<PanelCell>> {
+        let mut filled = vec![[None; PANEL_WIDTH]; rows.div_ceil(2) * 2];
+        if let Some(shape) = shape {
+            let extent = shape.orientation(shape.spawn_rotation());
+            let min_x = extent.iter().map(|&(x, _)| x).min().unwrap_or(0);
+            let min_y = extent.iter().map(|&(_, y)| y).min().unwrap_or(0);
+            for &(x, y) in extent {
+                let (x, y) = ((x - min_x) as usize + 1, (y - min_y) as usize);
+                if x < PANEL_WIDTH && y < filled.len() {
+                    filled[y][x] = Some(self.theme.piece(shape.kind()));
+                }
+            }
         }
+        filled
+            .chunks(2)
+            .map(|rows| {
+                (0..PANEL_WIDTH)
+                    .map(|x| match (rows[0][x], rows[1][x]) {
+                        (Some(top), bottom) => PanelCell { char: HALF_BLOCK, foreground: Some(top), background: bottom.unwrap_or(Color::Reset) },
+                        (None, Some(bottom)) => PanelCell { char: LOWER_HALF_BLOCK, foreground: Some(bottom), background: Color::Reset },
+                        (None, None) => PanelCell { char: ' ', foreground: None, background: Color::Reset },
+                    })
+                    .collect()
+            })
+            .collect()
     }
 
     /// Denna funktion hitta förändringar, skapar instructioner för dessa och utför dem. D.V.S. den updaterar block som förändrats.
-    pub fn draw(&mut self, data: &GameState) {
-        let changes = self.changes(data);
-        let instructions = self.instructions(changes);
-        self.execute(instructions);
+    pub fn draw(&mut self, data: &GameState) -> Result<(), TetrisError> {
+        self.set_kinds(data.shapes().iter().map(Shape::kind).collect());
+        self.draw_grid(self.next_grid(data))
+    }
+
+    /// Denna funktion byter vilka färger formerna ritas med. Om de ändrats målas allting om nästa gång något ritas.
+    fn set_kinds(&mut self, kinds: Vec<usize>) {
+        if self.kinds != kinds {
+            self.kinds = kinds;
+            self.grid = [[None;COLUMNS];ROWS];
+        }
     }
 
     /// Denna funktionen målar om alla block. Till skillnad från draw() så kollar den inte efter skillnad, utan målar om allting. Denna är användbar om något glitchat, t.ex. om man gör fönstret för litet och spelet buggar ut.
-    pub fn redraw(&mut self, data: &GameState) {
-        self.grid = [[usize::MAX;COLUMNS];ROWS];
-        self.draw(data);
+    pub fn redraw(&mut self, data: &GameState) -> Result<(), TetrisError> {
+        self.grid = [[None;COLUMNS];ROWS];
+        self.panel.clear();
+        self.border_changed = true;
+        self.draw(data)
+    }
+
+    /// Denna funktion målar en hel skärm med text, t.ex. en meny. Texten ersätter bakgrunden och centreras på en tom spelplan så att den ser ut som resten av spelet.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(lines): &[String] - raderna som skal visas, uppifrån och ned
+    /// Argument 3(grid): [[Cell;COLUMNS];ROWS] - block som visas bakom texten, t.ex. för att förhandsvisa ett tema. Här är talet i Cell::Piece en färg i temat och inte en form
+    ///
+    /// Exempel:
+    ///     output.draw_screen(&["Welcome to Tetris".to_string(), "> Play".to_string()], [[Cell::Empty;COLUMNS];ROWS])?;
+    pub fn draw_screen(&mut self, lines: &[String], grid: [[Cell;COLUMNS];ROWS]) -> Result<(), TetrisError> {
+        self.background_text = lines.join("\n");
+        self.background = Self::parse_background(&self.background_text, self.cell_size);
+        self.kinds = (0..self.theme.pieces.len()).collect();
+        self.grid = [[None;COLUMNS];ROWS];
+        self.border_changed = true;
+        self.draw_grid(grid)
+    }
+
+    /// Denna funktion hittar vilka block som skiljer sig från next_grid och målar endast dessa.
+    fn draw_grid(&mut self, next_grid: [[Cell;COLUMNS];ROWS]) -> Result<(), TetrisError> {
+        if !self.fits() { return Ok(()); }
+        self.update_overlay();
+        let changes = self.changes(next_grid);
+        let mut instructions = self.instructions(changes);
+        if self.border_changed {
+            instructions.extend(self.border());
+            self.border_changed = false;
+        }
+        self.execute(instructions)
     }
-    
-    /// Denna funktion använder bredden på skärmen för att beräkna ett offset så allting hamnar i mitten på skärmen.
-    fn offset(width: u16) -> (usize, usize) {
-        ((width as usize - (COLUMNS * BLOCK_WIDTH + 2)) / 2, 0)
+
+    /// Denna funktion använder bredden på skärmen för att beräkna ett offset så allting hamnar i mitten på skärmen. Offsetet är där spelplanen börjar, ramen ligger en kolumn till vänster om det.
+    fn offset(width: u16, cell_size: CellSize) -> (usize, usize) {
+        ((width as usize).saturating_sub(cell_size.required_size().0 as usize) / 2 + 1, 0)
+    }
+
+    /// Denna funktion ger den minsta storleken på terminalen som spelet går att spela i, alltså med den minsta cell storleken. (bredd, höjd)
+    pub fn required_size() -> (u16, u16) {
+        CellSize::HalfBlock.required_size()
     }
 
-    /// Denna funktion itererar genom en vector av instructioner och utför dessa. Den ställer tillbaka terminalen till dess grundvärden efter den är färdig.
-    fn execute(&self, instructions: Vec<Instruction>) {
-        for instruction in instructions {
-            instruction.perform().unwrap();
+    /// En getter för cell storleken som används just nu.
+    pub fn cell_size(&self) -> CellSize {
+        self.cell_size
+    }
+
+    /// Denna funktion kollar om spelplanen får plats i terminalen.
+    pub fn fits(&self) -> bool {
+        let (width, height) = self.cell_size.required_size();
+        self.size.0 >= width && self.size.1 >= height
+    }
+
+    /// Denna funktion anpassar output efter en ny storlek på terminalen. Den största cell storleken som får plats väljs och skärmen töms, allting målas om nästa gång något ritas eftersom spelplanen har flyttat sig.
+    ///
+    /// Om spelplanen inte får plats ens med de minsta cellerna visas istället ett meddelande om att terminalen behöver bli större.
+    ///
+    /// Argument 1(self): &mut self - en muterbar reference till output
+    /// Argument 2(width): u16 - terminalens nya bredd
+    /// Argument 3(height): u16 - terminalens nya höjd
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<(), TetrisError> {
+        let cell_size = CellSize::fitting(width, height).unwrap_or(CellSize::HalfBlock);
+        if cell_size != self.cell_size {
+            self.cell_size = cell_size;
+            self.background = Self::parse_background(&self.background_text, cell_size);
+            self.overlay = Self::parse_overlay(&self.messages, cell_size);
         }
-        execute!(stdout(), ResetColor, Hide, MoveTo((COLUMNS * BLOCK_WIDTH + self.offset.0) as u16, (ROWS * BLOCK_HEIGHT + self.offset.1) as u16)).unwrap();
+        self.size = (width, height);
+        self.offset = Self::offset(width, cell_size);
+        self.grid = [[None;COLUMNS];ROWS];
+        self.panel.clear();
+        self.border_changed = true;
+        let mut instructions = vec![Instruction::Clear];
+        if !self.fits() {
+            let (required_width, required_height) = Self::required_size();
+            let lines = [
+                "Terminal too small".to_string(),
+                format!("please enlarge to {required_width}x{required_height}"),
+                format!("(now {width}x{height})"),
+            ];
+            for (y, line) in lines.iter().enumerate() {
+                let x = (width as usize).saturating_sub(line.chars().count()) / 2;
+                let y = (height as usize / 2 + y).saturating_sub(1);
+                instructions.push(Instruction::MoveTo(x, y));
+                instructions.push(Instruction::Print(line.chars().take(width as usize).collect()));
+            }
+        }
+        self.renderer.execute(&instructions)?;
+        Ok(())
+    }
+
+    /// Denna funktion skickar en vector av instructioner till renderern som utför dessa. Instruktionerna optimeras först så att så lite som möjligt skickas. Markören flyttas sist ned under spelplanen.
+    fn execute(&mut self, mut instructions: Vec<Instruction>) -> Result<(), TetrisError> {
+        let (board_width, board_height) = self.cell_size.board_size();
+        instructions.push(Instruction::MoveTo(board_width + self.offset.0, board_height + self.offset.1));
+        self.renderer.execute(&Instruction::optimize(instructions))?;
+        Ok(())
+    }
+
+    /// Denna funktion visar allt som ritats sedan förra gången på en gång. Den anropas en gång per bild, efter både spelplanen och sidopanelen har ritats, så att en halvritad bild aldrig syns.
+    ///
+    /// Exempel:
+    ///     output.draw(&game)?;
+    ///     output.draw_hud(&game, now)?;
+    ///     output.flush()?;
+    pub fn flush(&mut self) -> Result<(), TetrisError> {
+        self.renderer.flush()?;
+        Ok(())
+    }
+
+    /// En getter för renderern, t.ex. för att läsa av vad som ritats till en Buffer.
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+}
+
+/// Ett meddelande i overlay lagret. expires är när meddelandet försvinner, None betyder att det visas tills det tas bort.
+struct Message {
+    text: String,
+    expires: Option<Instant>,
+}
+
+/// Ett tecken i sidopanelen. Om foreground är None används temats textfärg.
+#[derive(Clone, Copy, PartialEq)]
+struct PanelCell {
+    char: char,
+    foreground: Option<Color>,
+    background: Color,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spelplanen börjar en kolumn in i en 80x45 buffer, efter ramen. Cellerna är 4x2 tecken.
+    fn output() -> Output<Buffer> {
+        let output = Output::with_renderer(Buffer::new(80, 45)).unwrap();
+        assert_eq!(output.cell_size(), CellSize::Large);
+        output
+    }
+
+    /// Bakgrundsfärgen på det övre vänstra tecknet av en cell på den synliga spelplanen
+    fn background(output: &Output<Buffer>, x: usize, y: usize) -> Color {
+        output.renderer().cell(output.offset.0 + x * 4, y * 2).unwrap().background
+    }
+
+    #[test]
+    fn required_size_fits_the_board_border_and_panel() {
+        assert_eq!(CellSize::Large.required_size(), (79, 41));
+        assert_eq!(CellSize::Small.required_size(), (47, 21));
+        // sidopanelen är högre än den minsta spelplanen
+        assert_eq!(CellSize::HalfBlock.required_size(), (31, 13));
+    }
+
+    #[test]
+    fn fitting_picks_the_largest_size_that_fits() {
+        assert_eq!(CellSize::fitting(79, 41), Some(CellSize::Large));
+        assert_eq!(CellSize::fitting(200, 100), Some(CellSize::Large));
+        assert_eq!(CellSize::fitting(78, 41), Some(CellSize::Small));
+        assert_eq!(CellSize::fitting(79, 40), Some(CellSize::Small));
+        assert_eq!(CellSize::fitting(47, 21), Some(CellSize::Small));
+        assert_eq!(CellSize::fitting(46, 21), Some(CellSize::HalfBlock));
+        assert_eq!(CellSize::fitting(47, 20), Some(CellSize::HalfBlock));
+        assert_eq!(CellSize::fitting(31, 13), Some(CellSize::HalfBlock));
+        assert_eq!(CellSize::fitting(30, 13), None);
+        assert_eq!(CellSize::fitting(31, 12), None);
+    }
+
+    #[test]
+    fn too_small_terminal_shows_how_big_it_needs_to_be() {
+        let output = Output::with_renderer(Buffer::new(30, 13)).unwrap();
+        assert!(!output.fits());
+        let text = output.renderer().text();
+        assert!(text.contains("Terminal too small"), "{text}");
+        assert!(text.contains("(now 30x13)"), "{text}");
+    }
+
+    #[test]
+    fn draw_colors_locked_cells_by_their_shape() {
+        let mut game = GameState::new();
+        // T ligger inte på samma plats i bituppsättningen som sin färg, så fel färg syns direkt
+        let t = game.shapes().iter().position(|shape| shape.name() == 'T').unwrap();
+        assert_ne!(t, game.shapes()[t].kind());
+        game.grid[BOARD_ROWS - 1][0] = Cell::Piece(t);
+        game.grid[BOARD_ROWS - 1][1] = Cell::Garbage;
+
+        let mut output = output();
+        output.draw(&game).unwrap();
+        let theme = Theme::default();
+        assert_eq!(background(&output, 0, ROWS - 1), theme.piece(game.shapes()[t].kind()));
+        assert_eq!(background(&output, 1, ROWS - 1), theme.piece(GARBAGE_COLOR));
+        assert_eq!(background(&output, 2, ROWS - 1), theme.empty.1);
+        assert_eq!(background(&output, 3, ROWS - 1), theme.empty.0);
+        assert_eq!(output.renderer().cell(output.offset.0 - 1, 0).unwrap().background, theme.border);
+    }
+
+    #[test]
+    fn draw_shows_the_player() {
+        let mut game = GameState::new();
+        game.spawn();
+        let mut output = output();
+        output.draw(&game).unwrap();
+        let color = Theme::default().piece(game.shapes()[game.current].kind());
+        for (x, y) in game.player().unwrap().extent() {
+            assert_eq!(background(&output, x as usize, y as usize - HIDDEN_ROWS), color);
+        }
+    }
+
+    #[test]
+    fn draw_hud_writes_the_score() {
+        let mut game = GameState::new();
+        game.points = 400;
+        let mut output = output();
+        output.draw_hud(&game, Duration::from_secs(75)).unwrap();
+        let text = output.renderer().text();
+        assert!(text.contains("Score  00400"), "{text}");
+        assert!(text.contains("Time    1:15"), "{text}");
     }
 }
@@ -0,0 +1,286 @@
+use std::io::{stdout, Write};
+
+use crossterm::{execute, queue, terminal, Result};
+use crossterm::cursor::{Hide, MoveTo};
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+
+/// Detta är en representation av kommandon som skickas till en renderer
+///
+/// Varje typ av denna enum representerar ett execute!() kommand. och typerna i dessa (String, usize...) är anpassade fär att vara lättanvände i mitt syfte.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Instruction {
+    MoveTo(usize, usize),
+    Color(Color),
+    TextColor(Color),
+    Print(String),
+    Reset,
+    Clear,
+}
+
+impl Instruction {
+    /// Denna funktion tar bort onödiga instruktioner så att så lite som möjligt behöver skickas till terminalen.
+    ///
+    /// Den håller koll på var markören står och vilka färger som är valda. Färgbyten till en färg som redan är vald och förflyttningar till där markören redan står tas bort, och utskrifter som hamnar direkt efter varandra slås ihop till en.
+    ///
+    /// Argument 1(instructions): Vec<Instruction> - instruktionerna som ska optimeras
+    ///
+    /// Return: Vec<Instruction> - instruktioner som ger samma resultat
+    ///
+    /// Exempel:
+    ///     Instruction::optimize(vec![Color(Red), MoveTo(0, 0), Print("ab"), Color(Red), MoveTo(2, 0), Print("cd")]) -> [Color(Red), MoveTo(0, 0), Print("abcd")]
+    pub fn optimize(instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut optimized: Vec<Instruction> = Vec::new();
+        let mut cursor = None;
+        let mut color = None;
+        let mut text_color = None;
+        for instruction in instructions {
+            match instruction {
+                Instruction::MoveTo(x, y) => {
+                    if cursor == Some((x, y)) { continue; }
+                    cursor = Some((x, y));
+                }
+                Instruction::Color(next) => {
+                    if color == Some(next) { continue; }
+                    color = Some(next);
+                }
+                Instruction::TextColor(next) => {
+                    if text_color == Some(next) { continue; }
+                    text_color = Some(next);
+                }
+                Instruction::Print(ref text) => {
+                    cursor = cursor.map(|(x, y)| (x + text.chars().count(), y));
+                    if let Some(Instruction::Print(previous)) = optimized.last_mut() {
+                        previous.push_str(text);
+                        continue;
+                    }
+                }
+                Instruction::Reset | Instruction::Clear => {
+                    color = None;
+                    text_color = None;
+                }
+            }
+            optimized.push(instruction);
+        }
+        optimized
+    }
+}
+
+/// Denna trait beskriver något som output kan rita till
+///
+/// Output räknar ut vad som har ändrats och gör om det till instruktioner, en renderer utför sedan instruktionerna. På så sätt kan samma spel ritas till en riktig terminal eller till minnet (t.ex. för att spela in eller testa spelet).
+pub trait Renderer {
+    /// Denna funktion ger storleken på ytan som kan ritas på, i tecken. (bredd, höjd)
+    fn size(&self) -> Result<(u16, u16)>;
+
+    /// Denna funktion utför en lista av instruktioner i ordning. Det som ritas behöver inte synas förrän flush() anropas.
+    fn execute(&mut self, instructions: &[Instruction]) -> Result<()>;
+
+    /// Denna funktion visar allt som ritats sedan förra gången, den anropas en gång per bild.
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// En renderer som ritar till terminalen genom crossterm
+///
+/// Att skriva till terminalen är dyrt, speciellt över SSH. Därför köas alla kommandon i en buffer och skickas i ett enda svep när bilden är klar, så att en halvritad bild aldrig syns.
+pub struct Terminal {
+    buffer: Vec<u8>,
+}
+
+impl Terminal {
+    /// Denna funktion skapar en ny terminal renderer. Den tömmer skärmen så att vi börjar från en ren yta.
+    pub fn new() -> Result<Self> {
+        execute!(stdout(), MoveTo(0, 0), Clear(ClearType::FromCursorDown))?;
+        Ok(Terminal { buffer: Vec::new() })
+    }
+}
+
+impl Renderer for Terminal {
+    fn size(&self) -> Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    /// Denna funktion köar instruktionerna i buffern, inget skrivs till terminalen förrän flush() anropas.
+    fn execute(&mut self, instructions: &[Instruction]) -> Result<()> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::MoveTo(x, y) => queue!(self.buffer, MoveTo(*x as u16, *y as u16))?,
+                Instruction::Color(color) => queue!(self.buffer, SetBackgroundColor(*color))?,
+                Instruction::TextColor(color) => queue!(self.buffer, SetForegroundColor(*color))?,
+                Instruction::Print(text) => queue!(self.buffer, Print(text))?,
+                Instruction::Reset => queue!(self.buffer, ResetColor, Hide)?,
+                Instruction::Clear => queue!(self.buffer, ResetColor, Clear(ClearType::All))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Denna funktion skriver allt i buffern till terminalen på en gång och tömmer buffern. Den ger tillbaka (returnar) med antingen ett Ok() som betyder att den lyckades eller ett std::io::Error med ett meddelande om varför det inte fungerade.
+    fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() { return Ok(()); }
+        let mut stdout = stdout().lock();
+        stdout.write_all(&self.buffer)?;
+        self.buffer.clear();
+        stdout.flush()
+    }
+}
+
+/// En cell i en Buffer. Den sparar tecknet och båda färgerna som det ritades med.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BufferCell {
+    pub char: char,
+    pub background: Color,
+    pub foreground: Color,
+}
+
+impl Default for BufferCell {
+    fn default() -> Self {
+        BufferCell {
+            char: ' ',
+            background: Color::Reset,
+            foreground: Color::Reset,
+        }
+    }
+}
+
+/// En renderer som ritar till minnet istället för till en terminal
+///
+/// Den fungerar som en låtsas terminal med en markör och nuvarande färger. Allt som ritas hamnar i cells, som sedan kan läsas av med cell() eller text().
+pub struct Buffer {
+    width: usize,
+    height: usize,
+    cells: Vec<BufferCell>,
+    cursor: (usize, usize),
+    background: Color,
+    foreground: Color,
+}
+
+impl Buffer {
+    /// Denna funktion skapar en tom buffer med en viss storlek.
+    ///
+    /// Argument 1(width): usize - antal tecken per rad
+    /// Argument 2(height): usize - antal rader
+    ///
+    /// Exempel:
+    ///     Buffer::new(80, 45) -> Buffer { width: 80, height: 45, .. }
+    pub fn new(width: usize, height: usize) -> Self {
+        Buffer {
+            width,
+            height,
+            cells: vec![BufferCell::default(); width * height],
+            cursor: (0, 0),
+            background: Color::Reset,
+            foreground: Color::Reset,
+        }
+    }
+
+    /// Denna funktion ger cellen på en viss position, eller None om positionen är utanför bufferten.
+    pub fn cell(&self, x: usize, y: usize) -> Option<&BufferCell> {
+        if x < self.width && y < self.height {
+            self.cells.get(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    /// Denna funktion ger alla tecken i bufferten som text, en rad per rad i bufferten.
+    pub fn text(&self) -> String {
+        self.cells
+            .chunks(self.width)
+            .map(|row| row.iter().map(|cell| cell.char).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Renderer for Buffer {
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok((self.width as u16, self.height as u16))
+    }
+
+    /// Denna funktion utför instruktionerna på samma sätt som en terminal skulle göra. Tecken som hamnar utanför bufferten klipps bort.
+    fn execute(&mut self, instructions: &[Instruction]) -> Result<()> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::MoveTo(x, y) => self.cursor = (*x, *y),
+                Instruction::Color(color) => self.background = *color,
+                Instruction::TextColor(color) => self.foreground = *color,
+                Instruction::Print(text) => {
+                    for char in text.chars() {
+                        let (x, y) = self.cursor;
+                        if x < self.width && y < self.height {
+                            self.cells[y * self.width + x] = BufferCell {
+                                char,
+                                background: self.background,
+                                foreground: self.foreground,
+                            };
+                        }
+                        self.cursor.0 += 1;
+                    }
+                }
+                Instruction::Reset => {
+                    self.background = Color::Reset;
+                    self.foreground = Color::Reset;
+                }
+                Instruction::Clear => {
+                    self.background = Color::Reset;
+                    self.foreground = Color::Reset;
+                    self.cells.fill(BufferCell::default());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// En buffer visar allt direkt, så det finns inget att skriva ut.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Buffer, BufferCell, Instruction, Renderer};
+
+    use crossterm::style::Color;
+    use Instruction::*;
+
+    #[test]
+    fn optimize_merges_prints_next_to_each_other() {
+        let instructions = vec![
+            Color(Color::Red), MoveTo(0, 0), Print("ab".to_string()),
+            Color(Color::Red), MoveTo(2, 0), Print("cd".to_string()),
+        ];
+        assert_eq!(Instruction::optimize(instructions), vec![Color(Color::Red), MoveTo(0, 0), Print("abcd".to_string())]);
+    }
+
+    #[test]
+    fn optimize_keeps_prints_apart_when_the_cursor_or_color_changes() {
+        let instructions = vec![
+            MoveTo(0, 0), Print("ab".to_string()),
+            MoveTo(5, 0), Print("cd".to_string()),
+            Color(Color::Blue), Print("ef".to_string()),
+        ];
+        assert_eq!(Instruction::optimize(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn optimize_forgets_the_colors_after_a_reset() {
+        let instructions = vec![Color(Color::Red), Reset, Color(Color::Red), TextColor(Color::Red), TextColor(Color::Red)];
+        assert_eq!(Instruction::optimize(instructions), vec![Color(Color::Red), Reset, Color(Color::Red), TextColor(Color::Red)]);
+    }
+
+    #[test]
+    fn buffer_draws_like_a_terminal() {
+        let mut buffer = Buffer::new(4, 2);
+        buffer
+            .execute(&[MoveTo(1, 1), Color(Color::Blue), TextColor(Color::White), Print("abcd".to_string())])
+            .unwrap();
+        assert_eq!(buffer.text(), "    \n abc");
+        assert_eq!(buffer.cell(1, 1), Some(&BufferCell { char: 'a', background: Color::Blue, foreground: Color::White }));
+        assert_eq!(buffer.cell(4, 1), None);
+
+        buffer.execute(&[Clear]).unwrap();
+        assert_eq!(buffer.text(), "    \n    ");
+    }
+}
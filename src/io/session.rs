@@ -0,0 +1,63 @@
+use std::io::stdout;
+use std::panic::{self, PanicHookInfo};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crossterm::{execute, Result};
+use crossterm::cursor::{Hide, Show};
+use crossterm::style::ResetColor;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+
+/// Denna struct håller terminalen i spelläge så länge den finns
+///
+/// När den startas byter terminalen till en alternativ skärm, markören göms och raw mode slås på. När den släpps (drop) ställs allting tillbaka, oavsett om spelet avslutades, spelaren förlorade eller programmet kraschade. Vid en panic återställs terminalen av en panic hook innan felmeddelandet skrivs ut, annars skulle meddelandet hamna på den alternativa skärmen och försvinna.
+///
+/// Panic hooken som fanns innan sparas i previous. Vår hook anropar den efter terminalen ställts tillbaka, och när sessionen släpps installeras den igen.
+pub struct TerminalSession {
+    previous: Arc<Mutex<Option<PanicHook>>>,
+}
+
+/// En panic hook, samma typ som panic::take_hook() ger tillbaka
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+impl TerminalSession {
+    /// Denna funktion sätter terminalen i spelläge och installerar panic hooken.
+    ///
+    /// Return: Result<TerminalSession> - sessionen, eller ett fel om terminalen inte gick att ställa om
+    ///
+    /// Exempel:
+    ///     TerminalSession::start() -> Ok(TerminalSession)
+    pub fn start() -> Result<Self> {
+        let previous = Arc::new(Mutex::new(Some(panic::take_hook())));
+        let hook_previous = Arc::clone(&previous);
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            if let Some(hook) = hook_previous.lock().unwrap_or_else(|error| error.into_inner()).as_ref() {
+                hook(info);
+            }
+        }));
+        // sessionen skapas direkt efter hooken, så att den ställer tillbaka både hooken och terminalen om nästa steg misslyckas
+        let session = TerminalSession { previous };
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(session)
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        restore();
+        // set_hook får inte anropas under en panic, då är programmet ändå på väg att avslutas och vår hook gör ingen skada
+        if !thread::panicking() {
+            if let Some(hook) = self.previous.lock().unwrap_or_else(|error| error.into_inner()).take() {
+                panic::set_hook(hook);
+            }
+        }
+    }
+}
+
+/// Denna funktion ställer tillbaka terminalen som den var innan spelet startade. Fel ignoreras, det finns ändå inget bättre att göra med dem när programmet är på väg att avslutas.
+fn restore() {
+    let _ = execute!(stdout(), ResetColor, Show, LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use crossterm::style::Color;
+use json::JsonValue;
+
+use crate::{data_path, TetrisError};
+
+/// Mappen i data mappen som teman läses in från när spelet körs.
+const THEME_DIRECTORY: &str = "themes";
+/// Standard temat är inbakat i programmet så att det alltid finns minst ett tema, även om mappen saknas.
+const DEFAULT_THEME: &str = include_str!("../themes/classic.json");
+
+/// Denna struct beskriver vilka färger spelet ritas med
+///
+/// Fälten är:
+///     name: temats namn, det är detta som sparas i settings.json
+///     pieces: färgerna som bitarna ritas med, varje bit väljer en av dem i sin bituppsättning (se src/pieces). Om en bit väljer en färg utanför listan börjar den om från början
+///     empty: de två färgerna som tomma celler växlar mellan (rutnätet i bakgrunden)
+///     text: färgen på all text
+///     border: färgen på ramen runt spelplanen
+#[derive(Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub pieces: Vec<Color>,
+    pub empty: (Color, Color),
+    pub text: Color,
+    pub border: Color,
+}
+
+impl Theme {
+    /// Denna funktion läser in ett tema från json text. Färger kan skrivas som namn ("dark_grey") eller hex ("#1e0a0a").
+    ///
+    /// Argument 1(text): &str - innehållet i en tema fil
+    /// Argument 2(source): &str - var texten kommer ifrån, t.ex. filens sökväg. Den står i felet om något är fel
+    ///
+    /// Return: Result<Theme, TetrisError> - temat, eller ett fel som säger vad som är fel i filen
+    ///
+    /// Exempel:
+    ///     Theme::parse("{\"name\": \"mono\", \"pieces\": [\"white\"], \"empty\": [\"black\", \"black\"], \"text\": \"white\", \"border\": \"grey\"}", "mono.json") -> Ok(Theme { name: "mono", .. })
+    ///     Theme::parse("{\"name\": \"mono\"}", "mono.json") -> Err(TetrisError::Data("mono.json", "pieces should be a list of colors"))
+    pub fn parse(text: &str, source: &str) -> Result<Theme, TetrisError> {
+        let json = json::parse(text).map_err(|error| TetrisError::Parse(source.to_string(), error))?;
+        let error = |message: String| TetrisError::Data(source.to_string(), message);
+        let color = |value: &JsonValue, what: &str| match value.as_str() {
+            Some(text) => parse_color(text).ok_or_else(|| error(format!("{what} is {text}, which is not a color"))),
+            None => Err(error(format!("{what} should be a color"))),
+        };
+        let pieces = json["pieces"]
+            .members()
+            .map(|value| color(value, "a piece color"))
+            .collect::<Result<Vec<Color>, TetrisError>>()?;
+        if pieces.is_empty() {
+            return Err(error("pieces should be a list of colors".to_string()));
+        }
+        Ok(Theme {
+            name: json["name"].as_str().ok_or_else(|| error("name is missing".to_string()))?.to_string(),
+            pieces,
+            empty: (color(&json["empty"][0], "the first empty color")?, color(&json["empty"][1], "the second empty color")?),
+            text: color(&json["text"], "text")?,
+            border: color(&json["border"], "border")?,
+        })
+    }
+
+    /// Denna funktion läser in alla teman från tema mappen, sorterade efter namn. Bara .json filer läses, och om mappen saknas finns bara standard temat.
+    ///
+    /// Return: Result<Vec<Theme>, TetrisError> - alla teman, alltid minst standard temat. Ett fel om en fil inte går att läsa eller inte är ett giltigt tema
+    pub fn load_all() -> Result<Vec<Theme>, TetrisError> {
+        let directory = data_path(THEME_DIRECTORY);
+        let directory_error = |error| TetrisError::Io(directory.clone(), error);
+        let mut themes = Vec::new();
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![Theme::default()]),
+            Err(error) => return Err(directory_error(error)),
+        };
+        for entry in entries {
+            let path = entry.map_err(directory_error)?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let source = path.display().to_string();
+            let text = fs::read_to_string(&path).map_err(|error| TetrisError::Io(source.clone(), error))?;
+            themes.push(Theme::parse(&text, &source)?);
+        }
+        let default = Theme::default();
+        if !themes.iter().any(|theme| theme.name == default.name) {
+            themes.push(default);
+        }
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(themes)
+    }
+
+    /// Denna funktion hittar temat med ett visst namn. Om det inte finns används standard temat.
+    ///
+    /// Argument 1(name): &str - namnet på temat
+    ///
+    /// Return: Result<Theme, TetrisError> - temat med det namnet, eller ett fel om tema mappen inte gick att läsa in
+    ///
+    /// Exempel:
+    ///     Theme::find("red") -> Ok(Theme { name: "red", .. })
+    ///     Theme::find("finns inte") -> Ok(Theme { name: "classic", .. })
+    pub fn find(name: &str) -> Result<Theme, TetrisError> {
+        Ok(Self::load_all()?
+            .into_iter()
+            .find(|theme| theme.name == name)
+            .unwrap_or_default())
+    }
+
+    /// Denna funktion ger färgen som en viss sorts bit ska ha. Om temat har färre färger än det finns bitar börjar den om från början.
+    ///
+    /// Exempel:
+    ///     theme.piece(0) -> Color::DarkYellow
+    ///     theme.piece(5) -> Color::Cyan
+    pub fn piece(&self, kind: usize) -> Color {
+        self.pieces[kind % self.pieces.len()]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::parse(DEFAULT_THEME, "the built in theme").expect("the built in theme is valid")
+    }
+}
+
+/// Denna funktion konverterar text till en färg. Den förstår både crossterms färgnamn och hex koder.
+///
+/// Exempel:
+///     parse_color("dark_grey") -> Some(Color::DarkGrey)
+///     parse_color("#ff8000") -> Some(Color::Rgb { r: 255, g: 128, b: 0 })
+///     parse_color("lila") -> None
+fn parse_color(text: &str) -> Option<Color> {
+    match text.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 => {
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            Some(Color::Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+        }
+        Some(_) => None,
+        None => Color::try_from(text).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONO: &str = r##"{"name": "mono", "pieces": ["white", "#ff8000"], "empty": ["black", "dark_grey"], "text": "white", "border": "grey"}"##;
+
+    /// Felmeddelandet i ett TetrisError::Data, så att testerna kan se vad som var fel
+    fn message(result: Result<Theme, TetrisError>) -> String {
+        match result {
+            Err(TetrisError::Data(source, message)) => {
+                assert_eq!(source, "mono.json");
+                message
+            }
+            _ => panic!("expected a data error"),
+        }
+    }
+
+    #[test]
+    fn parse_reads_every_color() {
+        let theme = Theme::parse(MONO, "mono.json").unwrap();
+        assert_eq!(theme.name, "mono");
+        assert_eq!(theme.pieces, vec![Color::White, Color::Rgb { r: 255, g: 128, b: 0 }]);
+        assert_eq!(theme.empty, (Color::Black, Color::DarkGrey));
+        assert_eq!((theme.text, theme.border), (Color::White, Color::Grey));
+    }
+
+    #[test]
+    fn parse_reports_what_is_wrong() {
+        assert_eq!(message(Theme::parse(&MONO.replace("\"grey\"", "\"lila\""), "mono.json")), "border is lila, which is not a color");
+        assert_eq!(message(Theme::parse(&MONO.replace("\"text\"", "\"texts\""), "mono.json")), "text should be a color");
+        assert_eq!(message(Theme::parse(&MONO.replace("\"name\"", "\"names\""), "mono.json")), "name is missing");
+        assert_eq!(message(Theme::parse(&MONO.replace("[\"white\", \"#ff8000\"]", "[]"), "mono.json")), "pieces should be a list of colors");
+        assert!(matches!(Theme::parse("{", "mono.json"), Err(TetrisError::Parse(_, _))));
+    }
+
+    #[test]
+    fn piece_colors_wrap_around() {
+        let theme = Theme::parse(MONO, "mono.json").unwrap();
+        assert_eq!(theme.piece(1), theme.piece(3));
+        assert_eq!(theme.piece(2), Color::White);
+    }
+
+    #[test]
+    fn themes_in_the_theme_directory_load() {
+        let names: Vec<String> = Theme::load_all().unwrap().into_iter().map(|theme| theme.name).collect();
+        assert_eq!(names, ["black", "classic", "ocean", "red"]);
+    }
+
+    #[test]
+    fn built_in_theme_is_classic() {
+        assert_eq!(Theme::default().name, "classic");
+    }
+
+    #[test]
+    fn parse_color_reads_names_and_hex() {
+        assert_eq!(parse_color("dark_grey"), Some(Color::DarkGrey));
+        assert_eq!(parse_color("#1e0a0a"), Some(Color::Rgb { r: 30, g: 10, b: 10 }));
+        assert_eq!(parse_color("#1e0a0"), None);
+        assert_eq!(parse_color("#1e0a0g"), None);
+        assert_eq!(parse_color("lila"), None);
+    }
+}
@@ -1,72 +1,179 @@
 pub mod io;
-pub use io::{input::*, output::*};
+pub use io::{animation::*, input::*, output::*, renderer::*, session::*, theme::*};
+
+mod bitboard;
+pub use bitboard::*;
+
+mod error;
+pub use error::*;
+
+mod fumen;
+pub use fumen::*;
 
 mod menu;
 pub use menu::*;
 
+mod pieces;
+pub use pieces::*;
+
+mod randomizer;
+pub use randomizer::*;
+
+mod save;
+pub use save::*;
+
 mod utilities;
 pub use utilities::*;
 
+/// The directory every file the game reads or writes while it runs is kept in
+///
+/// The settings, the themes, the custom piece set, the saved game and exported fumens are all found here. The path is relative to where the game is run from, so the game should be started from the root of the repository, e.g. with cargo run.
+pub const DATA_DIRECTORY: &str = "./src";
+
+/// Gives the path of a file in the data directory
+///
+/// Example:
+///     data_path("save.json") -> "./src/save.json"
+pub fn data_path(file: &str) -> String {
+    format!("{DATA_DIRECTORY}/{file}")
+}
+
+/// A single cell on the board
+///
+/// Filled cells remember which piece they came from, as the index of its shape in the game's piece set. The color is looked up from the shape when the cell is drawn.
+/// Garbage is a block that isn't from any piece, e.g. from an imported fumen.
+/// Flash is never part of the game, it is only used by the output to draw animations.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Piece(usize),
+    Garbage,
+    Flash,
+}
+
 /// Define the size of the play area
 pub const ROWS: usize = 20;
 pub const COLUMNS: usize = 16;
+/// Rows above the play area that are never drawn. Pieces can be rotated and kicked into them, but a piece that locks there ends the game.
+pub const HIDDEN_ROWS: usize = 4;
+/// All rows of the board, the hidden rows first and then the visible ones
+pub const BOARD_ROWS: usize = HIDDEN_ROWS + ROWS;
+/// The level where pieces stop falling faster, like in the guideline. The formula for the fall speed stops working a long way above it.
+pub const MAX_SPEED_LEVEL: usize = 20;
+
+/// The ways a game can end because the stack reached the top
+///
+/// BlockOut: a new piece spawned on top of blocks that are already there
+/// LockOut: a piece locked with all of its blocks above the visible rows
+/// PartialLockOut: a piece locked with some of its blocks above the visible rows
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TopOut {
+    BlockOut,
+    LockOut,
+    PartialLockOut,
+}
+
+impl TopOut {
+    /// The name that is shown when the game ends
+    ///
+    /// Example:
+    ///     TopOut::LockOut.name() -> "lock out"
+    pub fn name(&self) -> &'static str {
+        match self {
+            TopOut::BlockOut => "block out",
+            TopOut::LockOut => "lock out",
+            TopOut::PartialLockOut => "partial lock out",
+        }
+    }
+}
 
 /// This struct stores all data about the current state of the game.
 /// 
 /// It also has usefull functions for manipulating the data and interacting with it.
 /// 
 /// fields:
-/// grid: [[usize;COLUMNS];ROWS] - a nested array with the gamaeboard grid
+/// grid: [[Cell;COLUMNS];BOARD_ROWS] - a nested array with the gamaeboard grid, the first HIDDEN_ROWS rows are above the visible play area. Filled cells hold the index of their shape in shapes
+/// bitboard: Bitboard - which cells of grid are filled, one integer per row. It is used for collisions and line clears and must always match grid
 /// player: Option<Player> - the moving block
 /// shapees: Vec<Shape> - A vec of all possible shapes
-/// shape_order: Vec<usize> - Saying which order the shapes should come
-/// points: usize - the amount of point accumelated
-/// 
-/// fields:
-/// grid: [[usize;COLUMNS];ROWS] - a nested array with the gamaeboard grid
-/// player: Option<Player> - the moving block
-/// shapees: Vec<Shape> - A vec of all possible shapes
-/// shape_order: Vec<usize> - Saying which order the shapes should come
+/// randomizer: Box<dyn Randomizer> - decides which shape comes next
+/// rng: GameRng - where all randomness in the game comes from
+/// upcoming: usize - the index of the shape that spawns next
 /// points: usize - the amount of point accumelated
+/// lines: usize - the amount of rows cleared
+/// piece_counts: Vec<usize> - how many of every piece that has spawned, in the same order as shapes
+/// pieces: usize - the amount of pieces that has been placed
+/// start_level: usize - the level the game started on, the level goes up by one every tenth line from there
+/// current: usize - the index of the players shape
+/// held: Option<usize> - the index of the shape in the hold slot
+/// hold_used: bool - if the player has already used hold since the last piece was placed
+/// clearing: Vec<usize> - full rows that are waiting to be removed
+/// top_out: Option<TopOut> - why the game ended, None while it is still going
 pub struct GameState {
-    grid: [[usize;COLUMNS];ROWS],
+    grid: [[Cell;COLUMNS];BOARD_ROWS],
+    bitboard: Bitboard,
     player: Option<Player>,
     shapes: Vec<Shape>,
-    shape_order: Vec<usize>,
+    randomizer: Box<dyn Randomizer>,
+    rng: GameRng,
+    upcoming: usize,
     pub points: usize,
+    pub lines: usize,
+    pub piece_counts: Vec<usize>,
+    pub pieces: usize,
+    pub start_level: usize,
+    current: usize,
+    held: Option<usize>,
+    hold_used: bool,
+    clearing: Vec<usize>,
+    top_out: Option<TopOut>,
 }
 
 impl GameState {
-    /// Create a new GameState with base values
+    /// Create a new GameState with base values, it is played with the classic pieces and the 7-bag randomizer
     /// 
     /// Return:
-    /// GameState{grid: [[usize;COLUMNS];ROWS], player: Option<Player>, shapes: Vec<Shape>, shape_order: Vec<usize>, point: usize} - The intlized state of the game
+    /// GameState - The intlized state of the game, with an empty board, no player and the first piece already picked
     /// 
     /// Example:
     /// 
     /// new()
-    /// #=> GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 0}
-    /// 
-    /// Return:
-    /// GameState{grid: [[usize;COLUMNS];ROWS], player: Option<Player>, shapes: Vec<Shape>, shape_order: Vec<usize>, point: usize} - The intlized state of the game
-    /// 
-    /// Example:
-    /// 
-    /// new()
-    /// #=> GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 0}
+    /// #=> GameState{grid: [[Cell::Empty;COLUMNS];BOARD_ROWS], player: None, shapes: [Shape{name: 'I', ..}, ..], upcoming: 3, points: 0, lines: 0, ..}
     pub fn new() -> Self {
-        let shapes = Shape::parse_shapes("");
-        Self {
-            grid: [[0;COLUMNS];ROWS],
+        let shapes = PieceSet::Classic.shapes().expect("the built in piece sets are valid");
+        Self::with_pieces(shapes, RandomizerKind::SevenBag.create())
+    }
+
+    /// Create a new GameState which plays with the given pieces, which piece comes next is up to the randomizer
+    ///
+    /// Argument 1: shapes: Vec<Shape> - the pieces of the game, e.g. from PieceSet::shapes
+    /// Argument 2: randomizer: Box<dyn Randomizer> - the randomizer which picks among them
+    ///
+    /// Example:
+    ///     GameState::with_pieces(PieceSet::Pentominoes.shapes()?, RandomizerKind::Nes.create())
+    pub fn with_pieces(shapes: Vec<Shape>, randomizer: Box<dyn Randomizer>) -> Self {
+        let pieces = shapes.len();
+        let mut game = Self {
+            grid: [[Cell::Empty;COLUMNS];BOARD_ROWS],
+            bitboard: Bitboard::new(),
             player: None,
             shapes,
-            shape_order: vec![3,2,2,1,0],
+            randomizer,
+            rng: GameRng::from_entropy(),
+            upcoming: 0,
             points: 0,
-        }
+            lines: 0,
+            piece_counts: vec![0; pieces],
+            pieces: 0,
+            start_level: 1,
+            current: 0,
+            held: None,
+            hold_used: false,
+            clearing: Vec::new(),
+            top_out: None,
+        };
+        game.upcoming = game.randomizer.next(&mut game.rng, game.shapes.len());
+        game
     }
 
     /// This method decides the points given when clearing rows
@@ -77,55 +184,13 @@ impl GameState {
     /// 
     /// Exemple:
     /// 
-    /// GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 0}.give_points(2)
-    /// #=> GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 400}
-    /// This method decides the points given when clearing rows
-    /// 
-    /// Argument:
-    /// self: GameState - An instance of Gamestate
-    /// rows_cleared: usize - How many rows which has been cleared.
-    /// 
-    /// Exemple:
-    /// 
-    /// GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 0}.give_points(2)
-    /// #=> GameState{grid: [[], [], [], [], [], [], [], []], player: None, shapes: Shape {extent: vec![
-    /// (-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, -1.0),],offset: (1.0, 1.0),}, shape_order: vec![3,2,2,1,0],
-    /// point: 400}
+    /// GameState{points: 0, ..}.give_points(2)
+    /// #=> GameState{points: 400, ..}
     fn give_points(&mut self, rows_cleared: usize) {
         self.points += 100 * 2usize.pow(rows_cleared as u32);
     }
 
-    /// This function fills the shape_order vector whenever it has been emptied
-    /// 
-    /// Argument: 
-    /// self: GameState - An instance of Gamestate 
-    /// 
-    /// 
-    /// Argument: 
-    /// self: GameState - An instance of Gamestate 
-    /// 
-    fn fill_shape_order(&mut self) {
-        let mut rng = rand::thread_rng();
-        let mut nums: Vec<usize> = (0..self.shapes.len().clone()).collect();
-        nums.shuffle(&mut rng);
-        self.shape_order = nums;
-    }
-
-    /// This method "consumes" one number in the shape_order vector.
-    /// 
-    /// Argument: 
-    /// self: GameState - An instance of Gamestate 
-    /// 
-    /// Return:
-    /// 
-    /// usize - the index of th next shape to use
-    /// This method "consumes" one number in the shape_order vector.
+    /// This method "consumes" the next shape and lets the randomizer pick the one after it, so that the next piece can always be shown.
     /// 
     /// Argument: 
     /// self: GameState - An instance of Gamestate 
@@ -134,18 +199,13 @@ impl GameState {
     /// 
     /// usize - the index of th next shape to use
     fn next_shape_index(&mut self) -> usize {
-        match self.shape_order.pop() {
-            Some(shape) => shape,
-            None => {
-                self.fill_shape_order();
-                self.next_shape_index()
-            },
-        }
+        let next = self.randomizer.next(&mut self.rng, self.shapes.len());
+        std::mem::replace(&mut self.upcoming, next)
     }
 
     /// This method spawnes a new player by gathering the next shapes index and then gathering the shape based on the index
-    /// Then getting the x and y cordinates of the shape and then the color
-    /// Then spawning the character
+    /// Then getting the x and y cordinates of the shape
+    /// Then spawning the character and counting it in the statistics
     /// 
     /// 
     /// Argument: 
@@ -154,10 +214,84 @@ impl GameState {
     /// 
     pub fn spawn(&mut self) {
         let shape_index = self.next_shape_index();
-        let shape = self.shapes[shape_index].clone();
-        let (x, y) = ((COLUMNS as f32 / 2.0 - shape.get_offset().0) as i32, (0.0) as i32);
-        let color = thread_rng().gen_range(1..COLORS.len());
-        self.player = Some(Player::spawn(x, y, shape, color));
+        self.piece_counts[shape_index] += 1;
+        self.spawn_shape(shape_index);
+    }
+
+    /// This method places a new player with a specific shape at the position and rotation the shape spawns with. If it overlaps blocks that are already there the game ends with a block out.
+    ///
+    /// Argument 1: self: GameState - An instance of Gamestate
+    /// Argument 2: shape_index: usize - the index of the shape in shapes
+    fn spawn_shape(&mut self, shape_index: usize) {
+        let shape = self.shapes[shape_index].spawn_orientation();
+        let (x, y) = shape.spawn();
+        let y = y + HIDDEN_ROWS as i32;
+        self.current = shape_index;
+        self.player = Some(Player::spawn(x, y, shape));
+        if self.collision().is_some() {
+            self.top_out = Some(TopOut::BlockOut);
+        }
+    }
+
+    /// This method puts the player in the hold slot. The piece that was held before comes back at the top of the board, or the next piece spawns if the slot was empty.
+    ///
+    /// Hold can only be used once per piece, it becomes available again when a piece is placed.
+    ///
+    /// Argument:
+    /// self: GameState - An instance of Gamestate
+    ///
+    /// Return: bool - true if the pieces were swapped
+    ///
+    /// Example:
+    ///     game.hold() -> true
+    ///     game.hold() -> false
+    pub fn hold(&mut self) -> bool {
+        if self.hold_used || self.player.is_none() {
+            return false;
+        }
+        self.hold_used = true;
+        match self.held.replace(self.current) {
+            Some(held) => self.spawn_shape(held),
+            None => self.spawn(),
+        }
+        true
+    }
+
+    /// A getter for the shape in the hold slot, as it is written in the piece data. Use Shape::spawn_orientation() to get it the way it spawns
+    pub fn held(&self) -> Option<&Shape> {
+        self.held.map(|index| &self.shapes[index])
+    }
+
+    /// A getter for the shapes of the game, the index in a Cell::Piece points into this
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    /// A getter for the shape that spawns next
+    pub fn next(&self) -> Option<&Shape> {
+        self.shapes.get(self.upcoming)
+    }
+
+    /// This method calculates the current level from the starting level and the amount of cleared lines.
+    ///
+    /// Example:
+    ///     game.level() -> 1
+    ///     game.level() -> 4
+    pub fn level(&self) -> usize {
+        self.start_level + self.lines / 10
+    }
+
+    /// This method gives the time between each automatic fall of the player. It gets shorter for every level, starting at one second on level 1.
+    /// Levels above MAX_SPEED_LEVEL fall as fast as MAX_SPEED_LEVEL.
+    ///
+    /// Return: Duration - the time between falls
+    ///
+    /// Example:
+    ///     game.fall_interval() -> 1s
+    ///     game.fall_interval() -> 355ms
+    pub fn fall_interval(&self) -> Duration {
+        let level = self.level().clamp(1, MAX_SPEED_LEVEL) as f64 - 1.0;
+        Duration::from_secs_f64((0.8 - level * 0.007).powf(level))
     }
 
     /// This method checks for collisions, if it finds one it returns a value from an Enum with what type of collission, if non were found it returns None
@@ -173,20 +307,21 @@ impl GameState {
     ///     self.collision() -> Some(Collision::Block)
     ///     self.collision() -> None
     fn collision(&self) -> Option<Collision> {
-        if let Some(player) = &self.player {
-            for (x, y) in player.extent() {
-                if x < 0 { return Some(Collision::Wall) }
-                if x >= COLUMNS as i32 { return Some(Collision::Wall) }
-                if y >= ROWS as i32 { return Some(Collision::Floor) }
-                if y < 0 { continue; }
-                let x = x as usize;
-                let y = y as usize;
-                if self.grid[y][x] != 0 { return Some(Collision::Block); }
-            }
-            None
-        } else {
-            None
-        }
+        self.bitboard.collision(self.player.as_ref()?.extent())
+    }
+
+    /// This method gives how many rows the player can fall before it lands, it is how far a hard drop moves it
+    ///
+    /// Example:
+    ///     game.drop_distance() -> 17
+    ///     game.drop_distance() -> 0
+    pub fn drop_distance(&self) -> i32 {
+        self.player.as_ref().map_or(0, |player| self.bitboard.drop_distance(player.extent()))
+    }
+
+    /// A getter for which cells of the board are filled, for bots and anything else that only needs to know that
+    pub fn bitboard(&self) -> &Bitboard {
+        &self.bitboard
     }
 
     /// This function manipulates the player field to move it around based on the type and values of the PlayerMove enum argument
@@ -211,43 +346,122 @@ impl GameState {
     pub fn try_move(&mut self, player_move: PlayerMove) -> Option<Collision> {
         self.do_move(&player_move);
         let collision = self.collision();
-        if let Some(_) = collision {
+        if collision.is_some() {
             self.do_move(&player_move.opposite());
         }
         collision
     }
 
-    /// This function Goes through all the positions the player extends to and sets the grid[y][x] at these positions to the players color.
+    /// This method tries to rotate the player. If the rotated piece collides, it is moved by each of the wall kicks for that rotation until one of them fits. If none of them fits the player goes back to its original rotation.
+    ///
+    /// Argument 1: self: GameState - An instance of Gamestate
+    /// Argument 2: angle: i32 - How many quarter turns clockwise, negative for counter-clockwise
+    ///
+    /// Return: Option<Collision> - None if the player was rotated, otherwise the collision of the last kick
+    ///
+    /// Example:
+    ///     game.try_rotate(1) -> None
+    ///     game.try_rotate(-1) -> Some(Collision::Wall)
+    ///     game.try_rotate(2) -> None
+    pub fn try_rotate(&mut self, angle: i32) -> Option<Collision> {
+        let kicks = self.player.as_ref()?.kicks(angle);
+        self.do_move(&PlayerMove::Rotate(angle));
+        let mut collision = None;
+        for (dx, dy) in kicks {
+            let kick = PlayerMove::Translate(dx, dy);
+            self.do_move(&kick);
+            match self.collision() {
+                None => return None,
+                kick_collision => collision = kick_collision,
+            }
+            self.do_move(&kick.opposite());
+        }
+        self.do_move(&PlayerMove::Rotate(-angle));
+        collision
+    }
+
+    /// This function Goes through all the positions the player extends to and sets the grid[y][x] at these positions to the index of the players shape.
     fn stamp(&mut self) {
         if let Some(player) = &self.player {
             for (x, y) in player.extent() {
                 let x = x as usize;
                 let y = y as usize;
-                if y >= ROWS || x >= COLUMNS { continue; }
-                self.grid[y][x] = player.color;
+                if y >= BOARD_ROWS || x >= COLUMNS { continue; }
+                self.grid[y][x] = Cell::Piece(self.current);
+                self.bitboard.fill(x as i32, y as i32);
             }
         }
     }
 
     /// This function stamps all the players blocks onto the grid. It also checks for cleared rows and gives points for these. The player field is left at None indicating the the player is gone.
+    ///
+    /// The cleared rows stay on the board until clear_lines() is called, so that they can be animated first.
+    ///
+    /// If any of the players blocks are still above the visible rows once the cleared rows are removed the game ends with a lock out, or a partial lock out if only some of them are.
+    ///
+    /// Nothing happens if there is no player, e.g. during entry delay.
     pub fn kill_player(&mut self) {
+        if self.player.is_none() { return; }
         self.stamp();
+        self.pieces += 1;
+        self.hold_used = false;
         let cleared_rows = self.find_cleared_rows();
-        if cleared_rows.len() > 0 {
+        self.top_out = self.top_out.or(self.lock_out(&cleared_rows));
+        if !cleared_rows.is_empty() {
             self.give_points(cleared_rows.len());
-            self.fill_cleared_rows(cleared_rows);
+            self.lines += cleared_rows.len();
+            self.clearing = cleared_rows;
         }
         self.player = None;
     }
 
+    /// This function checks if the player locked above the visible rows. Blocks in cleared rows disappear, and blocks above cleared rows move down one row for every cleared row below them.
+    fn lock_out(&self, cleared_rows: &[usize]) -> Option<TopOut> {
+        let player = self.player.as_ref()?;
+        let rows: Vec<usize> = player
+            .extent()
+            .map(|(_, y)| y as usize)
+            .filter(|y| !cleared_rows.contains(y))
+            .map(|y| y + cleared_rows.iter().filter(|&&cleared| cleared > y).count())
+            .collect();
+        let hidden = rows.iter().filter(|&&y| y < HIDDEN_ROWS).count();
+        if hidden == 0 {
+            None
+        } else if hidden == rows.len() {
+            Some(TopOut::LockOut)
+        } else {
+            Some(TopOut::PartialLockOut)
+        }
+    }
+
+    /// A getter for why the game ended, None while the game is still going
+    ///
+    /// Example:
+    ///     game.top_out() -> None
+    ///     game.top_out() -> Some(TopOut::BlockOut)
+    pub fn top_out(&self) -> Option<TopOut> {
+        self.top_out
+    }
+
+    /// A getter for the full rows that are waiting to be removed
+    pub fn clearing(&self) -> &[usize] {
+        &self.clearing
+    }
+
+    /// This function removes the full rows that kill_player() found and moves the rows above them down.
+    pub fn clear_lines(&mut self) {
+        let cleared_rows = std::mem::take(&mut self.clearing);
+        self.fill_cleared_rows(cleared_rows);
+    }
+
     /// This function finds all rows that have been cleared. It only checks the rows which the player is occupying to save processing time.
     fn find_cleared_rows(&self) -> Vec<usize> {
         let mut cleared_rows = Vec::new();
         if let Some(player) = &self.player {
             for (_, y) in player.extent() {
                 let y = y as usize;
-                if y >= ROWS { continue; }
-                if !self.grid[y].contains(&0) && !cleared_rows.contains(&y) {
+                if y >= BOARD_ROWS { continue; }
+                if self.bitboard.full(y) && !cleared_rows.contains(&y) {
                     cleared_rows.push(y);
                 }
             }
@@ -261,25 +475,114 @@ impl GameState {
     /// It iterates over all rows and displaces them by the jump_height variable. the jump_height variable increments by one every time we iterate on a cleared row.
     fn fill_cleared_rows(&mut self, cleared_rows: Vec<usize>) {
         let mut jump_length = 1;
-        if cleared_rows.len() == 0 { return; }
+        if cleared_rows.is_empty() { return; }
         for y in (0..*cleared_rows.last().unwrap()).rev() {
             if cleared_rows.contains(&y) {
                 jump_length += 1;
             } else {
                 self.grid[y + jump_length] = self.grid[y];
+                self.bitboard.copy_row(y, y + jump_length);
             }
         }
 
         for  y in 0..jump_length {
-            self.grid[y] = [0;COLUMNS];
+            self.grid[y] = [Cell::Empty;COLUMNS];
+            self.bitboard.clear_row(y);
         }
     }
 
     /// This is something called a getter. It's a way for users of the GameStruct to access the structs private fields
     pub fn alive(&self) -> bool {
-        match self.player {
-            Some(_) => true,
-            None => false,
+        self.player.is_some()
+    }
+
+    /// A getter for the moving block
+    pub fn player(&self) -> Option<&Player> {
+        self.player.as_ref()
+    }
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills a cell in both the grid and the bitboard, the way a locked piece would
+    fn fill(game: &mut GameState, x: usize, y: usize) {
+        game.grid[y][x] = Cell::Garbage;
+        game.bitboard.fill(x as i32, y as i32);
+    }
+
+    /// Places a piece in its spawn orientation with its leftmost column at x and its top row at y
+    fn place(game: &mut GameState, name: char, x: i32, y: i32) {
+        let index = game.shapes.iter().position(|shape| shape.name() == name).unwrap();
+        let shape = game.shapes[index].spawn_orientation();
+        let player = Player::spawn(0, 0, shape.clone());
+        let min_x = player.extent().map(|(x, _)| x).min().unwrap();
+        let min_y = player.extent().map(|(_, y)| y).min().unwrap();
+        game.current = index;
+        game.player = Some(Player::spawn(x - min_x, y - min_y, shape));
+    }
+
+    #[test]
+    fn spawning_on_top_of_blocks_is_a_block_out() {
+        let mut game = GameState::new();
+        for y in 0..HIDDEN_ROWS + 4 {
+            for x in 0..COLUMNS {
+                fill(&mut game, x, y);
+            }
+        }
+        game.spawn();
+        assert_eq!(game.top_out(), Some(TopOut::BlockOut));
+    }
+
+    #[test]
+    fn spawning_on_an_empty_board_is_not_a_top_out() {
+        let mut game = GameState::new();
+        game.spawn();
+        assert_eq!(game.top_out(), None);
+        assert!(game.player().unwrap().extent().all(|(_, y)| y >= HIDDEN_ROWS as i32));
+    }
+
+    #[test]
+    fn locking_entirely_in_the_hidden_rows_is_a_lock_out() {
+        let mut game = GameState::new();
+        place(&mut game, 'O', 4, HIDDEN_ROWS as i32 - 2);
+        game.kill_player();
+        assert_eq!(game.top_out(), Some(TopOut::LockOut));
+    }
+
+    #[test]
+    fn locking_partly_in_the_hidden_rows_is_a_partial_lock_out() {
+        let mut game = GameState::new();
+        place(&mut game, 'O', 4, HIDDEN_ROWS as i32 - 1);
+        game.kill_player();
+        assert_eq!(game.top_out(), Some(TopOut::PartialLockOut));
+    }
+
+    #[test]
+    fn locking_in_the_visible_rows_is_not_a_top_out() {
+        let mut game = GameState::new();
+        place(&mut game, 'O', 4, HIDDEN_ROWS as i32);
+        game.kill_player();
+        assert_eq!(game.top_out(), None);
+    }
+
+    #[test]
+    fn a_piece_that_comes_down_with_cleared_rows_is_not_a_top_out() {
+        let mut game = GameState::new();
+        // the O piece has one row in the hidden rows and completes the first visible row
+        for x in (0..COLUMNS).filter(|&x| x != 4 && x != 5) {
+            fill(&mut game, x, HIDDEN_ROWS);
         }
+        place(&mut game, 'O', 4, HIDDEN_ROWS as i32 - 1);
+        game.kill_player();
+        assert_eq!(game.clearing(), &[HIDDEN_ROWS]);
+        assert_eq!(game.top_out(), None);
     }
 }
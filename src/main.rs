@@ -1,30 +1,112 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
 use tetris::*;
 
 // förbättring: parsing av shapes frånm text-fil, inställningar som faktiskt fungerar, simplifiering av kod och bättre struktur
 // bra sak: radflyttnigs algoritm
 
 //mani() funktionen körs automatiskt av rust och används för att köra all annan kod som behöver köras
-fn main() {
-    // Startar menyn och sparar structen som returneras (som element 2 av en tuple)
-    // i en mutable variabel
-    let mut setting = Settings::start().unwrap();
+fn main() -> ExitCode {
+    // om något går fel har terminalen redan ställts tillbaka när run() returnerar, så felet syns efter spelet
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("tetris: {error}");
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
+
+// run() kör menyn och spelet. Fel skickas vidare med ? istället för att krascha, main() skriver ut dem
+fn run() -> Result<(), TetrisError> {
+    // En fumen kan ges som argument, t.ex. `tetris v115@...`. Ett nytt spel startar då med den som spelplan, som träning
+    // utan att poängen hamnar på highscore listan.
+    // Den läses innan terminalen ställs om så att ett fel syns direkt.
+    let fumen = env::args().nth(1).map(|text| Fumen::parse(&text)).transpose()?;
+
+    // Ställer terminalen i spelläge. När session släpps ställs terminalen tillbaka, även om programmet kraschar
+    let session = TerminalSession::start()?;
+    // Startar input tråden, den används av både menyn och spelet så länge programmet körs
+    let input = Input::start();
+
+    // Startar menyn och sparar inställningarna och hur spelet ska starta.
+    // Menyn ritas på en egen output, spelet får en ny när menyn är klar.
+    let (setting, play) = match Settings::start(&mut Output::new()?, &input)? {
+        Some(start) => start,
+        None => {
+            // terminalen måste ställas tillbaka innan något skrivs ut
+            drop(session);
+            println!("Buh, Bye!");
+            return Ok(());
+        }
+    };
 
-    // Initierar struct för att hantera spel data, och output data.
-    let mut game = GameState::new();
-    let mut output = Output::new();
+    // Ett nytt spel använder spelläget, bitarna och randomizern som valts för spelläget. Ett sparat spel fortsätter
+    // med de som det startades med, och börjar pausat så att spelaren hinner förbereda sig.
+    let continued = matches!(play, Play::Continue(_));
+    let saved = match play {
+        Play::Continue(saved) => *saved,
+        Play::New(user) => {
+            let mut game = GameState::with_pieces(setting.pieces().shapes()?, setting.randomizer().create());
+            game.start_level = setting.difficulty() as usize;
+            if let Some(fumen) = &fumen {
+                fumen.apply(&mut game)?;
+            }
+            SavedGame {
+                game,
+                user,
+                mode: setting.mode(),
+                pieces: setting.pieces(),
+                randomizer: setting.randomizer(),
+                time: Duration::ZERO,
+                fall_timer: Duration::ZERO,
+                // första biten kommer efter att "Ready..." har visats
+                respawn_at: Duration::from_millis(1000),
+                clear_at: Duration::ZERO,
+                ranked: fumen.is_none(),
+            }
+        }
+    };
+    // timers i spelets tid. clear_at är när raderna som rensats ska tas bort, line clear delay efter att biten låstes
+    let SavedGame { mut game, mut user, mode, pieces, randomizer, time, mut fall_timer, mut respawn_at, mut clear_at, ranked } = saved;
+
+    // Initierar output data.
+    let mut output = Output::new()?;
+    output.set_theme(Theme::find(setting.theme())?);
+    output.set_animation_speed(setting.animations());
 
     // Eftersom inga block rört sig ännu kommer inte output att måla något. Vi måsta be den att måla bakgrunden genom denna metod.
-    output.redraw(&game);
+    if continued {
+        output.show_message("Paused", None);
+        // ett sparat spel har redan en spelplan, så en fumen som getts som argument används inte
+        if fumen.is_some() {
+            output.show_message("Fumen ignored", Some(Duration::from_millis(3000)));
+        }
+    } else {
+        output.show_message("Ready...", Some(Duration::from_millis(1000)));
+    }
+    output.redraw(&game)?;
+    // sidopanelen ritas annars bara när spelet inte är pausat, ett sparat spel börjar pausat
+    output.draw_hud(&game, time)?;
 
-    // timers
-    let mut respawn_timer = Instant::now()
-        .checked_sub(Duration::from_secs(999))
-        .unwrap();
-    let mut fall_timer = Instant::now();
+    // spelets klocka, den står still medan spelet är pausat. Ett sparat spel fortsätter från tiden det sparades
+    let mut clock = Clock::starting_at(time);
+    if continued {
+        clock.pause();
+    }
     let mut redraw_timer = Instant::now();
+    // när senaste bilden ritades, och om en bild hoppades över för att den kom för tidigt
+    let frame_time = setting.frame_time();
+    let mut frame_timer = Instant::now();
+    let mut frame_skipped = false;
+    // om spelaren har pausat
+    let mut paused = continued;
 
-    // fps räknare
-    let mut fps = Fps::new(Duration::from_millis(1000));
+    // rader och nivå från förra varvet, för att se när ett meddelande ska visas
+    let mut lines = game.lines;
+    let mut level = game.level();
 
     // Detta är själva loopen. Här använder vi alla våra hjälpmoduler med hjälpfunktioner och structs för att skriva spel logiken med konsis syntax.
     //
@@ -32,76 +114,247 @@ fn main() {
     // Om vi får problem med glitchar i utseendet kan jag till exempel vara väldigt säker på att det är någt fel i output modulen. Att lättare kunna felsöka är ett massivt plus.
     //
     // Exakt hur vi delade upp här är inte lika viktigt som att vi delat upp projektet över huvudtaget. Tycker jag i alla fall.
-    'game_loop: loop {
-        // hantera input event
-        if let Some(input) = input() {
-            let _ = match input {
+    // loopen ger tillbaka meddelandet som skrivs ut när spelet är slut och spelets tid när det tog slut
+    let (ending, end) = 'game_loop: loop {
+        // räknar ut hur länge loopen kan sova. Den vaknar när nästa sak i spelet ska hända, när output behöver
+        // ritas om eller direkt när input kommer. Den sover aldrig kortare än tiden till nästa bild får ritas
+        let frame_wait = frame_time.saturating_sub(frame_timer.elapsed());
+        let timeout = if frame_skipped {
+            frame_wait
+        } else {
+            let now = clock.now();
+            let game_event = if !game.clearing().is_empty() {
+                clear_at.saturating_sub(now)
+            } else if !game.alive() {
+                respawn_at.saturating_sub(now)
+            } else {
+                (fall_timer + game.fall_interval()).saturating_sub(now)
+            };
+            // tiden i sidopanelen visas i hela sekunder
+            let next_second = Duration::from_secs(now.as_secs() + 1) - now;
+            let redraw = Duration::from_secs(3).saturating_sub(redraw_timer.elapsed());
+            // inget i spelet händer medan det är pausat, bara meddelanden kan försvinna. Animationerna står still med klockan
+            let deadlines = if !output.fits() || paused {
+                vec![Some(Duration::from_secs(60)), output.next_message()]
+            } else {
+                vec![Some(game_event), Some(next_second), Some(redraw), output.next_update()]
+            };
+            deadlines.into_iter().flatten().min().unwrap_or_default().max(frame_wait)
+        };
+
+        // allt som ritades under förra varvet visas på en gång innan loopen sover
+        output.flush()?;
+
+        // hantera alla input event som har kommit sedan förra varvet, i ordning
+        for (time, event) in input.events(timeout)? {
+            // spelets tid när knappen trycktes
+            let time = clock.at(time);
+            match event {
+                InputEvent::Resize(width, height) => {
+                    output.resize(width, height)?;
+                    if output.fits() {
+                        output.redraw(&game)?;
+                        output.draw_hud(&game, clock.now())?;
+                    }
+                }
+                // spelet sparas så att det kan fortsätta från menyn nästa gång
+                InputEvent::Quit => {
+                    // en bit som låstes tidigare i samma omgång kan redan ha avslutat spelet, då slutar det som vanligt och sparas inte
+                    if let Some(reason) = game.top_out() {
+                        break 'game_loop (format!("game over, {}", reason.name()), clock.now());
+                    }
+                    let saved = SavedGame { game, user, mode, pieces, randomizer, time: clock.now(), fall_timer, respawn_at, clear_at, ranked };
+                    saved.save()?;
+                    drop(session);
+                    println!("Game saved, choose Continue in the menu to play on. Buh, Bye!");
+                    return Ok(());
+                }
+                InputEvent::Pause => {
+                    paused = !paused;
+                    if paused {
+                        output.show_message("Paused", None);
+                    } else {
+                        output.hide_message("Paused");
+                    }
+                    output.draw(&game)?;
+                }
+                // spelplanen sparas som en fumen, så att den kan delas
+                InputEvent::Export => {
+                    // en fumen är bara 10 kolumner bred, block utanför den skulle försvinna så då sparas inget.
+                    // Om filen inte går att skriva fortsätter spelet, det är inte värt att förlora spelet för
+                    let exported = Fumen::from_game(&game).is_ok_and(|fumen| fs::write(data_path(FUMEN_FILE), fumen.encode()).is_ok());
+                    let message = if exported { "Fumen saved" } else { "Can't export" };
+                    output.show_message(message, Some(Duration::from_millis(1500)));
+                    // loopen ritar inget medan spelet är pausat, så meddelandet måste ritas här
+                    if paused && output.fits() {
+                        output.draw(&game)?;
+                    }
+                }
+                // ingen får röra sig medan spelet är pausat
+                _ if !output.fits() || paused => (),
                 InputEvent::Left => {game.try_move(PlayerMove::Translate(-1, 0));},
                 InputEvent::Right => {game.try_move(PlayerMove::Translate(1, 0));},
-                InputEvent::Rotate => {game.try_move(PlayerMove::Rotate(1));},
+                InputEvent::RotateCw => {game.try_rotate(1);},
+                InputEvent::RotateCcw => {game.try_rotate(-1);},
+                InputEvent::Rotate180 => {game.try_rotate(2);},
                 InputEvent::Drop => {
-                    for _ in 0..ROWS {
-                        game.try_move(PlayerMove::Translate(0, 1));
+                    // utan spelare finns inget att släppa, t.ex. under entry delay eller medan rader rensas
+                    let Some(player) = game.player() else { continue };
+                    let start: Vec<(i32, i32)> = player.extent().collect();
+                    let distance = game.drop_distance();
+                    game.try_move(PlayerMove::Translate(0, distance));
+                    // svansen går från där biten var till där den landade, uppifrån och ned
+                    let mut trail: Vec<(usize, usize)> = start
+                        .iter()
+                        .flat_map(|&(x, y)| (y..y + distance).map(move |y| (x, y)))
+                        .filter(|&(x, y)| x >= 0 && y >= 0)
+                        .map(|(x, y)| (x as usize, y as usize))
+                        .collect();
+                    trail.sort_by_key(|&(x, y)| (y, x));
+                    trail.dedup();
+                    output.animate(Animation::Trail(trail));
+                    if let Some(timers) = lock(&mut game, &mut output, &setting, time) {
+                        (clear_at, respawn_at) = timers;
                     }
-                    game.kill_player();
-                    respawn_timer = Instant::now();
                 }
                 InputEvent::Down => {
-                    if let Some(_) = game.try_move(PlayerMove::Translate(0, 1)) {
-                        game.kill_player();
-                        respawn_timer = Instant::now();
+                    if game.try_move(PlayerMove::Translate(0, 1)).is_some() {
+                        if let Some(timers) = lock(&mut game, &mut output, &setting, time) {
+                            (clear_at, respawn_at) = timers;
+                        }
                     }
-                    fall_timer = Instant::now();
+                    fall_timer = time;
                 }
-                InputEvent::Quit => {
-                    println!("Buh, Bye!");
-                    break 'game_loop;
+                InputEvent::Hold => {
+                    if game.hold() {
+                        fall_timer = time;
+                    }
                 }
-            };
+            }
+        }
+
+        // spelet är slut om en bit låstes ovanför spelplanen eller inte fick plats när den kom
+        if let Some(reason) = game.top_out() {
+            break 'game_loop (format!("game over, {}", reason.name()), clock.now());
+        }
+
+        // pausar spelet medan terminalen är för liten eller spelaren har pausat. Klockan står still så ingen tid går under pausen
+        if !output.fits() || paused {
+            clock.pause();
+            // meddelanden som har gått ut tas bort från skärmen även under pausen
+            if output.fits() {
+                output.draw(&game)?;
+            }
+            continue 'game_loop;
         }
+        clock.resume();
+        let now = clock.now();
 
-        // respawn om tillräckligt med tid har passerat och spelaren är död
-        if !game.alive() && respawn_timer.elapsed() > Duration::from_millis(500) {
+        // tar bort rensade rader när deras animation är klar
+        if !game.clearing().is_empty() && now >= clear_at {
+            game.clear_lines();
+        }
+
+        // respawn när entry delay har passerat, spelaren är död och inga rader håller på att rensas
+        if !game.alive() && game.clearing().is_empty() && now >= respawn_at {
             game.spawn();
-            if let Some(_) = game.try_move(PlayerMove::Translate(0, 0)) {
-                println!("game over");
-                break 'game_loop;
+            if game.pieces == 0 {
+                output.show_message("Go!", Some(Duration::from_millis(500)));
             }
-            fall_timer = Instant::now();
+            fall_timer = now;
         }
 
-        // automatiskt fall varje sekund
-        if game.alive() && fall_timer.elapsed() > Duration::from_millis(1000) {
-            if let Some(_) = game.try_move(PlayerMove::Translate(0, 1)) {
-                game.kill_player();
-                respawn_timer = Instant::now();
+        // avslutar spelet om målet för spelläget är uppnått
+        if mode.finished(&game, now) {
+            break 'game_loop (format!("{} finished in {:.1}s", mode.name(), now.as_secs_f64()), now);
+        }
+
+        // automatiskt fall, snabbare ju högre nivå
+        if game.alive() && now.saturating_sub(fall_timer) > game.fall_interval() {
+            if game.try_move(PlayerMove::Translate(0, 1)).is_some() {
+                if let Some(timers) = lock(&mut game, &mut output, &setting, now) {
+                    (clear_at, respawn_at) = timers;
+                }
             }
-            fall_timer = Instant::now();
+            fall_timer = now;
+        }
+        if let Some(reason) = game.top_out() {
+            break 'game_loop (format!("game over, {}", reason.name()), clock.now());
         }
 
+        // visar ett meddelande när flera rader rensas på en gång eller nivån går upp
+        let message = match game.lines - lines {
+            2 => Some("Double"),
+            3 => Some("Triple"),
+            4 => Some("TETRIS!"),
+            _ => None,
+        };
+        if let Some(message) = message {
+            output.show_message(message, Some(Duration::from_millis(1500)));
+        }
+        if game.level() > level {
+            output.show_message(&format!("Level {}", game.level()), Some(Duration::from_millis(1500)));
+        }
+        lines = game.lines;
+        level = game.level();
+
+        // ritar inte oftare än frame cap tillåter, bilden ritas istället när det är dags
+        frame_skipped = frame_timer.elapsed() < frame_time;
+        if frame_skipped {
+            continue 'game_loop;
+        }
+        frame_timer = Instant::now();
+
         // målar om hela output var tredje sekund för att motverka glitcher
+        output.tick(now);
         if redraw_timer.elapsed() > Duration::from_secs(3) {
-            output.redraw(&game);
+            output.redraw(&game)?;
             redraw_timer = Instant::now();
         } else {
-            output.draw(&game);
+            output.draw(&game)?;
         }
 
-        // sparar poäng i settings
-        setting.1.score = game.points as u32;
+        // målar sidopanelen med poäng, nivå, hold osv.
+        output.draw_hud(&game, now)?;
+    };
 
-        // målar poängen till skärmen
-        output.draw_score(format!("{points:0>5}", points = game.points));
-        // visar fps i vänstra hörnet
-        output.draw_fps(format!("fps {fps:.0}", fps = fps.fps));
+    // terminalen måste ställas tillbaka innan något skrivs ut
+    drop(session);
+    println!("{ending}");
 
-        // updaterar bild räkningen
-        fps.frame();
+    //den nuvarande användarens poäng läggs till (med insert()) i spellägets Highscore struct
+    //som laddas in med Settings::load_json().0. Om poängen räckte till en placering får vi tillbaka vilken plats.
+    //Poängen räknas ut först nu så att även den sista biten kommer med. I Sprint är poängen tiden, en sprint som inte blev klar kommer inte med.
+    //Ett spel från en fumen är träning och kommer inte med på listan
+    let mut highscore = Settings::load_json()?.0;
+    if !ranked {
+        println!("Practice game from a fumen, the score isn't saved");
+    } else if let Some(score) = mode.score(&game, end) {
+        user.score = score;
+        if let Some(rank) = highscore[mode.index()].insert(user) {
+            println!("New {} highscore! You placed #{rank}", mode.name());
+        }
     }
-
-    //den nuvarande användarens poäng läggs till (med append()) i Highscore structen
-    //som laddas in med Settings::load_json().0 och sparar resulterande Highscore i en variabel
-    let highscore = Settings::load_json().0.append(setting.1);
     //skriver innehållet av variabeln till .json filen
-    Settings::save_json(setting.0, highscore);
+    Settings::save_json(&setting, &highscore)
+}
+
+// Låser spelaren på plats och startar animationerna för det. Ger tillbaka när raderna som rensades ska tas bort
+// och när nästa bit ska komma. Rader tas bort efter line clear delay och nästa bit kommer entry delay efter det.
+// Om det inte finns någon spelare händer inget och timers ska inte ändras, då ger den tillbaka None.
+fn lock(game: &mut GameState, output: &mut Output, setting: &Settings, now: Duration) -> Option<(Duration, Duration)> {
+    let cells: Vec<(usize, usize)> = game
+        .player()?
+        .extent()
+        .filter(|&(x, y)| x >= 0 && y >= 0)
+        .map(|(x, y)| (x as usize, y as usize))
+        .collect();
+    game.kill_player();
+    output.animate(Animation::Lock(cells));
+    if game.clearing().is_empty() {
+        return Some((now, now + setting.entry_delay()));
+    }
+    let clear_at = now + setting.line_clear_delay();
+    output.animate_for(Animation::Clear(game.clearing().to_vec()), setting.line_clear_delay());
+    Some((clear_at, clear_at + setting.entry_delay()))
 }
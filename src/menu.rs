@@ -1,20 +1,221 @@
-use crossterm::event::{read, Event, KeyCode, KeyEventKind};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use crate::*;
 use json::object;
 use std::fs;
-use std::io;
-use std::io::Write;
+
+/// The file in the data directory which the settings and the highscores are stored in
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// How many rows that has to be cleared to finish a sprint
+pub const SPRINT_LINES: usize = 40;
+/// How long an ultra game lasts
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+/// The game modes that can be chosen in the menu
+///
+/// Marathon goes on until the player tops out, Sprint ends when SPRINT_LINES rows are cleared
+/// and Ultra ends when ULTRA_TIME has passed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Marathon,
+    Sprint,
+    Ultra,
+}
+
+impl Mode {
+    /// All modes in the order they are shown in the menu
+    pub const ALL: [Mode; 3] = [Mode::Marathon, Mode::Sprint, Mode::Ultra];
+
+    /// The name of the mode as it is shown in the menu and saved in settings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "Marathon",
+            Mode::Sprint => "Sprint",
+            Mode::Ultra => "Ultra",
+        }
+    }
+
+    /// The position of the mode in Mode::ALL
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|mode| mode == self).unwrap_or(0)
+    }
+
+    /// A short explanation of the mode which is shown in the menu
+    pub fn description(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "Play until the stack reaches the top",
+            Mode::Sprint => "Clear 40 lines as fast as you can",
+            Mode::Ultra => "Score as much as you can in 2 minutes",
+        }
+    }
+
+    /// Finds the mode with the given name
+    ///
+    /// Arguments:
+    ///
+    /// name: &str - The name of the mode, the case doesn't matter
+    ///
+    /// Return:
+    ///
+    /// Option<Mode> - The mode or None if there is no mode with that name
+    ///
+    /// Example:
+    ///
+    /// Mode::parse("sprint")
+    /// # => Some(Mode::Sprint)
+    pub fn parse(name: &str) -> Option<Mode> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Checks if the goal of the mode has been reached and the game should end
+    ///
+    /// Arguments:
+    ///
+    /// game: &GameState - The game that is being played
+    /// elapsed: Duration - How long the game has been going on
+    ///
+    /// Return:
+    ///
+    /// bool - true if the game is finished
+    pub fn finished(&self, game: &GameState, elapsed: Duration) -> bool {
+        match self {
+            Mode::Marathon => false,
+            Mode::Sprint => game.lines >= SPRINT_LINES,
+            Mode::Ultra => elapsed >= ULTRA_TIME,
+        }
+    }
+
+    /// If a lower score ranks higher on the leaderboard of the mode. Sprint is a race, so its score is the time it took
+    pub fn lowest_first(&self) -> bool {
+        *self == Mode::Sprint
+    }
+
+    /// The score a game gets on the leaderboard of the mode
+    ///
+    /// Marathon and Ultra are ranked by points and Sprint by how many milliseconds it took to clear SPRINT_LINES rows.
+    ///
+    /// Arguments:
+    ///
+    /// game: &GameState - The game that ended
+    /// elapsed: Duration - How long the game went on
+    ///
+    /// Return:
+    ///
+    /// Option<u32> - The score, or None if it can't be ranked because it is a sprint that wasn't finished
+    ///
+    /// Example:
+    ///
+    /// Mode::Sprint.score(&game, Duration::from_millis(62500))
+    /// # => Some(62500)
+    pub fn score(&self, game: &GameState, elapsed: Duration) -> Option<u32> {
+        match self {
+            Mode::Sprint if !self.finished(game, elapsed) => None,
+            Mode::Sprint => Some(elapsed.as_millis().min(u32::MAX as u128) as u32),
+            _ => Some(game.points as u32),
+        }
+    }
+
+    /// How a score on the leaderboard of the mode is shown
+    ///
+    /// Example:
+    ///
+    /// Mode::Sprint.format_score(62500)
+    /// # => "1:02.50"
+    /// Mode::Marathon.format_score(400)
+    /// # => "400"
+    pub fn format_score(&self, score: u32) -> String {
+        match self {
+            Mode::Sprint => {
+                let millis = score % 60000;
+                format!("{}:{:0>2}.{:0>2}", score / 60000, millis / 1000, millis % 1000 / 10)
+            }
+            _ => score.to_string(),
+        }
+    }
+}
+
+/// How fast the animations in the game are played
+///
+/// Off skips the animations completely and Fast plays them in half the time. The line clear animation is the exception, it always lasts as long as the line clear delay.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationSpeed {
+    Off,
+    Normal,
+    Fast,
+}
+
+impl AnimationSpeed {
+    /// All speeds in the order they are cycled through in the menu
+    pub const ALL: [AnimationSpeed; 3] = [AnimationSpeed::Off, AnimationSpeed::Normal, AnimationSpeed::Fast];
+
+    /// The name of the speed as it is shown in the menu and saved in settings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimationSpeed::Off => "Off",
+            AnimationSpeed::Normal => "Normal",
+            AnimationSpeed::Fast => "Fast",
+        }
+    }
+
+    /// Finds the speed with the given name, the case doesn't matter
+    ///
+    /// Example:
+    ///
+    /// AnimationSpeed::parse("fast")
+    /// # => Some(AnimationSpeed::Fast)
+    pub fn parse(name: &str) -> Option<AnimationSpeed> {
+        Self::ALL
+            .into_iter()
+            .find(|speed| speed.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Scales how long an animation lasts
+    ///
+    /// Arguments:
+    ///
+    /// length: Duration - How long the animation lasts at normal speed
+    ///
+    /// Return:
+    ///
+    /// Option<Duration> - How long it lasts at this speed, None if animations are off
+    ///
+    /// Example:
+    ///
+    /// AnimationSpeed::Fast.scale(Duration::from_millis(400))
+    /// # => Some(200ms)
+    pub fn scale(&self, length: Duration) -> Option<Duration> {
+        match self {
+            AnimationSpeed::Off => None,
+            AnimationSpeed::Normal => Some(length),
+            AnimationSpeed::Fast => Some(length / 2),
+        }
+    }
+}
 
 /// Stores all of settings for the session
 ///
 /// Fields:
-/// difficulty: u32 - Stores a number between 0-9 which holds the difficulty level
-/// color: char - Holds a character which represents the color theme which is used
-
-#[derive(Clone, Copy)]
+/// difficulty: u32 - Stores a number between 1-9 which holds the difficulty level
+/// theme: String - The name of the color theme which is used
+/// mode: Mode - The game mode which is played
+/// animations: AnimationSpeed - How fast animations are played, or if they are played at all
+/// frame_cap: u32 - The most frames per second that are drawn
+/// entry_delay: u32 - Milliseconds between a piece locking and the next one spawning (ARE)
+/// line_clear_delay: u32 - Milliseconds that cleared lines stay on the board before they are removed
+/// randomizers: [RandomizerKind; 3] - The randomizer of every mode, in the same order as Mode::ALL
+/// pieces: [PieceSet; 3] - The piece set of every mode, in the same order as Mode::ALL
+#[derive(Clone)]
 pub struct Settings {
     difficulty: u32,
-    color: char,
+    theme: String,
+    mode: Mode,
+    animations: AnimationSpeed,
+    frame_cap: u32,
+    entry_delay: u32,
+    line_clear_delay: u32,
+    randomizers: [RandomizerKind; 3],
+    pieces: [PieceSet; 3],
 }
 
 /// Stores a user with an assisted score and name
@@ -45,245 +246,662 @@ impl User {
     /// 
     /// add_user(String.from("Carl"))
     /// # => User{name: "Carl", score: 0}
-    fn add_user(name: String) -> Self {
+    pub fn add_user(name: String) -> Self {
         Self { name, score: 0 }
     }
+
+    /// A getter for the name of the user
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
 
-/// Stores the top 10 highest scores
+/// The frame caps that can be chosen in the menu
+pub const FRAME_CAPS: [u32; 5] = [30, 60, 120, 144, 240];
+/// The frame cap that is used if nothing else is configured
+pub const DEFAULT_FRAME_CAP: u32 = 60;
+
+/// The entry delays in milliseconds that can be chosen in the menu, 0 is for competitive play and the longest ones are for classic rulesets
+pub const ENTRY_DELAYS: [u32; 6] = [0, 100, 200, 300, 500, 800];
+/// The entry delay that is used if nothing else is configured
+pub const DEFAULT_ENTRY_DELAY: u32 = 500;
+/// The line clear delays in milliseconds that can be chosen in the menu
+pub const LINE_CLEAR_DELAYS: [u32; 6] = [0, 200, 300, 400, 700, 1000];
+/// The line clear delay that is used if nothing else is configured
+pub const DEFAULT_LINE_CLEAR_DELAY: u32 = 400;
+
+/// The amounts of entries a leaderboard can keep that can be chosen in the menu
+pub const HIGHSCORE_CAPACITIES: [usize; 5] = [5, 10, 20, 50, 100];
+/// The amount of entries a leaderboard keeps if nothing else is configured
+pub const DEFAULT_HIGHSCORE_CAPACITY: usize = 10;
+
+/// Stores a ranked leaderboard of the best scores, every mode has its own
 ///
 /// feilds:
-/// users: Vec<User> - A vec with multiple users, the vec should be orderd by biggest scores first
+/// users: Vec<User> - A vec with multiple users, orderd by best scores first. Users with the same score keep the order they placed in
+/// capacity: usize - The maximum amount of users the leaderboard keeps
+/// lowest_first: bool - If the lowest score is the best, e.g. for times in Sprint. Otherwise the biggest score is the best
 #[derive(Clone)]
 pub struct Highscores {
     users: Vec<User>,
+    capacity: usize,
+    lowest_first: bool,
 }
 
 /// Methods for Highscores
 impl Highscores {
-    /// append adds a new user to the Highscore structs user vec
+    /// Creates a new empty leaderboard
+    ///
+    /// Arguments:
+    ///
+    /// capacity: usize - How many users the leaderboard should keep
+    /// lowest_first: bool - If the lowest score is the best, see Mode::lowest_first
+    ///
+    /// Return:
+    ///
+    /// Highscores - A new instance of the struct Highscores without any users
+    ///
+    /// Example:
+    ///
+    /// Highscores::new(5, false)
+    /// # => Highscores{users: [], capacity: 5, lowest_first: false}
+    pub fn new(capacity: usize, lowest_first: bool) -> Self {
+        Self {
+            users: Vec::new(),
+            capacity,
+            lowest_first,
+        }
+    }
+
+    /// insert places a new user on the leaderboard
     ///
-    /// This out of place method creates a new Highscore struct with the added user.
-    /// If there are more than 10 users in the struct it will compare the new user
-    /// to the user which has lowest score and if it has higher it will remove that user.
-    /// Then will it sort the vec.
+    /// The user is placed after every user with a better or equal score, so an earlier
+    /// score wins a tie. If the leaderboard is full the last user is removed.
+    /// If the score isn't high enough to place, the leaderboard is left unchanged.
     ///
     /// Arguments:
     ///
-    /// self: Highscores - which is the struct which is calling this method
+    /// self: &mut Highscores - which is the struct which is calling this method
     /// user: User - which holds the struct user that stores a score and a name
     ///
     /// Return:
     ///
-    /// Settings - A new instance of the struct Highscore
-    /// 
+    /// Option<usize> - The rank the user placed at, starting from 1, or None if the score didn't place
+    ///
     /// Example:
-    /// 
-    /// Highscore{users: [User{name: "Carl", score: 20}]}.append(User{name: "Tore": 50})
-    /// # => Highscore{users: [User{name: "Tore", score: 50}, User{name: "Carl", score: 20}]}
-    pub fn append(&mut self, user: User) -> Self {
-        let length = self.users.len();
-        if self
+    ///
+    /// Highscores{users: [User{name: "Carl", score: 20}], capacity: 10, lowest_first: false}.insert(User{name: "Tore", score: 50})
+    /// # => Some(1)
+    /// Highscores{users: [User{name: "Carl", score: 20}], capacity: 1, lowest_first: false}.insert(User{name: "Tore", score: 20})
+    /// # => None
+    /// Highscores{users: [User{name: "Carl", score: 20}], capacity: 10, lowest_first: true}.insert(User{name: "Tore", score: 50})
+    /// # => Some(2)
+    pub fn insert(&mut self, user: User) -> Option<usize> {
+        let index = self
             .users
             .iter()
-            .min_by_key(|x| x.score)
-            .unwrap_or(&User {
-                score: 0,
-                name: "".to_string(),
-            })
-            .score
-            < user.score
-            || length <= 10
-        {
-            if length == 10 {
-                self.users.remove(0);
+            .position(|x| if self.lowest_first { x.score > user.score } else { x.score < user.score })
+            .unwrap_or(self.users.len());
+        if index >= self.capacity {
+            return None;
+        }
+        self.users.insert(index, user);
+        self.users.truncate(self.capacity);
+        Some(index + 1)
+    }
+
+    /// Changes how many users the leaderboard keeps. Users that no longer fit are removed.
+    ///
+    /// Arguments:
+    ///
+    /// capacity: usize - The new maximum amount of users
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.users.truncate(capacity);
+    }
+
+    /// A getter for the maximum amount of users the leaderboard keeps
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// A getter for the users on the leaderboard, orderd by rank
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+}
+
+/// The different screens of the start menu
+#[derive(Clone, Copy, PartialEq)]
+enum Screen {
+    Main,
+    Modes,
+    Settings,
+    Controls,
+    Highscores,
+    Name,
+}
+
+impl Screen {
+    /// The screens which can be opened from the main screen, in the order they are listed
+    const MAIN: [Screen; 5] = [Screen::Name, Screen::Modes, Screen::Settings, Screen::Controls, Screen::Highscores];
+}
+
+/// What the player decided to do in the menu
+enum Choice {
+    Play(User),
+    Continue,
+    Quit,
+}
+
+/// How the game starts once the menu is left
+///
+/// New(User) - A new game is started by the user
+/// Continue(Box<SavedGame>) - The saved game is continued where it was quit
+pub enum Play {
+    New(User),
+    Continue(Box<SavedGame>),
+}
+
+/// The longest name a user can enter
+const NAME_LENGTH: usize = 16;
+
+/// Stores the state of the start menu
+///
+/// Fields:
+/// screen: Screen - The screen that is shown
+/// selected: usize - The index of the highlighted item on the screen
+/// name: String - The name that is being typed in
+/// settings: Settings - The settings which are being changed
+/// high_scores: [Highscores; 3] - The leaderboard of every mode, in the same order as Mode::ALL. The one of the chosen mode is shown
+/// themes: Vec<Theme> - All themes which can be chosen
+/// saved: bool - If there is a saved game, then Continue is the first item on the main screen
+/// notice: Option<String> - A message shown on the main screen, e.g. when the saved game couldn't be continued
+struct Menu {
+    screen: Screen,
+    selected: usize,
+    name: String,
+    settings: Settings,
+    high_scores: [Highscores; 3],
+    themes: Vec<Theme>,
+    saved: bool,
+    notice: Option<String>,
+}
+
+impl Menu {
+    /// The items which can be selected on the current screen
+    ///
+    /// Return:
+    ///
+    /// Vec<String> - The text of every item
+    ///
+    /// Example:
+    ///
+    /// Menu{screen: Screen::Settings, ..}.items()
+    /// # => ["Difficulty  < 5 >", "Theme  < classic >", "Animations  < Normal >", "Frame cap  < 60 >", "Entry delay  < 500 ms >", "Line clear delay  < 400 ms >", "Marathon randomizer  < 7-bag >", "Marathon pieces  < Classic >", "Highscores kept  < 10 >", "Back"]
+    fn items(&self) -> Vec<String> {
+        let mut items: Vec<String> = match self.screen {
+            Screen::Main => ["Continue", "Play", "Modes", "Settings", "Controls", "Highscores", "Quit"]
+                .into_iter()
+                .skip(self.main_offset())
+                .map(String::from)
+                .collect(),
+            Screen::Modes => Mode::ALL.iter().map(|mode| mode.name().to_string()).collect(),
+            Screen::Settings => vec![
+                format!("Difficulty  < {} >", self.settings.difficulty),
+                format!("Theme  < {} >", self.settings.theme),
+                format!("Animations  < {} >", self.settings.animations.name()),
+                format!("Frame cap  < {} >", self.settings.frame_cap),
+                format!("Entry delay  < {} ms >", self.settings.entry_delay),
+                format!("Line clear delay  < {} ms >", self.settings.line_clear_delay),
+                format!("{} randomizer  < {} >", self.settings.mode.name(), self.settings.randomizer().name()),
+                format!("{} pieces  < {} >", self.settings.mode.name(), self.settings.pieces().name()),
+                format!("Highscores kept  < {} >", self.high_scores[0].capacity()),
+            ],
+            Screen::Controls | Screen::Highscores => vec![],
+            Screen::Name => return vec![],
+        };
+        if self.screen != Screen::Main {
+            items.push("Back".to_string());
+        }
+        items
+    }
+
+    /// The lines of text which describe the current screen, shown between the title and the items
+    fn info(&self) -> Vec<String> {
+        match self.screen {
+            Screen::Main => {
+                let mut lines = vec![format!("Mode: {}", self.settings.mode.name())];
+                if let Some(notice) = &self.notice {
+                    lines.extend([String::new(), notice.clone()]);
+                }
+                lines
+            }
+            Screen::Modes => {
+                let mode = Mode::ALL.get(self.selected).unwrap_or(&self.settings.mode);
+                vec!["Modes".to_string(), String::new(), mode.description().to_string()]
             }
-            self.users.push(user.clone());
-            self.users.sort_by_key(|x| x.score);
-            self.users.reverse();
+            Screen::Settings => vec!["Settings".to_string()],
+            Screen::Controls => {
+                let mut lines = vec!["Controls".to_string(), String::new()];
+                for (action, keys) in controls() {
+                    lines.push(format!("{action:<12}{keys:>18}"));
+                }
+                lines
+            }
+            Screen::Highscores => {
+                let mode = self.settings.mode;
+                let high_scores = &self.high_scores[mode.index()];
+                let mut lines = vec![format!("{} highscores", mode.name()), String::new()];
+                for (rank, user) in high_scores.users().iter().enumerate() {
+                    lines.push(format!("{:>2}. {:<16}{:>8}", rank + 1, user.name, mode.format_score(user.score)));
+                }
+                if high_scores.users().is_empty() {
+                    lines.push("No highscores yet".to_string());
+                }
+                lines
+            }
+            Screen::Name => vec![
+                "Enter your name".to_string(),
+                String::new(),
+                format!("{:_<width$}", self.name, width = NAME_LENGTH),
+                String::new(),
+                "Enter to start, Esc to go back".to_string(),
+            ],
         }
-        Self {
-            users: self.users.clone(),
+    }
+
+    /// Composes all the text on the current screen
+    ///
+    /// Return:
+    ///
+    /// Vec<String> - The lines which should be drawn, the selected item is marked with arrows
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec!["T E T R I S".to_string(), String::new()];
+        lines.extend(self.info());
+        lines.push(String::new());
+        for (index, item) in self.items().into_iter().enumerate() {
+            if index == self.selected {
+                lines.push(format!("> {item} <"));
+            } else {
+                lines.push(item);
+            }
+        }
+        lines
+    }
+
+    /// How many items on the main screen are skipped, Continue is only shown when there is a saved game
+    fn main_offset(&self) -> usize {
+        if self.saved { 0 } else { 1 }
+    }
+
+    /// The theme which is chosen in the settings
+    ///
+    /// Return:
+    ///
+    /// Theme - The chosen theme, or the default theme if it doesn't exist anymore
+    fn theme(&self) -> Theme {
+        self.themes
+            .iter()
+            .find(|theme| theme.name == self.settings.theme)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The blocks which are drawn behind the text. On the settings screen a small stack is drawn so
+    /// the colors of the chosen theme can be previewed.
+    ///
+    /// Return:
+    ///
+    /// [[Cell; COLUMNS]; ROWS] - A grid with a couple of blocks of every piece kind
+    fn preview(&self) -> [[Cell; COLUMNS]; ROWS] {
+        let mut grid = [[Cell::Empty; COLUMNS]; ROWS];
+        if self.screen == Screen::Settings {
+            let colors = self.theme().pieces.len();
+            for (x, cell) in grid[ROWS - 1].iter_mut().enumerate() {
+                if x != COLUMNS - 3 {
+                    *cell = Cell::Piece(x / 2 % colors);
+                }
+            }
+            for (x, cell) in grid[ROWS - 2].iter_mut().take(COLUMNS / 2).enumerate() {
+                *cell = Cell::Piece((x / 2 + COLUMNS / 2) % colors);
+            }
+        }
+        grid
+    }
+
+    /// Shows another screen with the first item selected
+    fn open(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.selected = match screen {
+            Screen::Modes => Mode::ALL.iter().position(|mode| *mode == self.settings.mode).unwrap_or(0),
+            _ => 0,
+        };
+    }
+
+    /// Goes back to the main screen with the screen we came from selected
+    fn back(&mut self) {
+        let from = self.screen;
+        self.open(Screen::Main);
+        self.selected = Screen::MAIN.iter().position(|screen| *screen == from).map_or(0, |index| index + 1 - self.main_offset());
+    }
+
+    /// Changes the setting which is selected up or down
+    ///
+    /// Arguments:
+    ///
+    /// step: i32 - 1 to go to the next value and -1 to go to the previous one
+    fn change_setting(&mut self, step: i32) {
+        match self.selected {
+            0 => self.settings.difficulty = (self.settings.difficulty as i32 + step).clamp(1, 9) as u32,
+            1 => {
+                let index = self.themes.iter().position(|theme| theme.name == self.settings.theme).unwrap_or(0) as i32;
+                let index = (index + step).rem_euclid(self.themes.len() as i32) as usize;
+                self.settings.theme = self.themes[index].name.clone();
+            }
+            2 => {
+                let speeds = AnimationSpeed::ALL;
+                let index = speeds.iter().position(|speed| *speed == self.settings.animations).unwrap_or(0) as i32;
+                self.settings.animations = speeds[(index + step).rem_euclid(speeds.len() as i32) as usize];
+            }
+            3 => {
+                let index = FRAME_CAPS.iter().position(|cap| *cap == self.settings.frame_cap).unwrap_or(0) as i32;
+                self.settings.frame_cap = FRAME_CAPS[(index + step).rem_euclid(FRAME_CAPS.len() as i32) as usize];
+            }
+            4 => {
+                let index = ENTRY_DELAYS.iter().position(|delay| *delay == self.settings.entry_delay).unwrap_or(0) as i32;
+                self.settings.entry_delay = ENTRY_DELAYS[(index + step).rem_euclid(ENTRY_DELAYS.len() as i32) as usize];
+            }
+            5 => {
+                let index = LINE_CLEAR_DELAYS.iter().position(|delay| *delay == self.settings.line_clear_delay).unwrap_or(0) as i32;
+                self.settings.line_clear_delay = LINE_CLEAR_DELAYS[(index + step).rem_euclid(LINE_CLEAR_DELAYS.len() as i32) as usize];
+            }
+            6 => {
+                let kinds = RandomizerKind::ALL;
+                let index = kinds.iter().position(|kind| *kind == self.settings.randomizer()).unwrap_or(0) as i32;
+                let mode = self.settings.mode.index();
+                self.settings.randomizers[mode] = kinds[(index + step).rem_euclid(kinds.len() as i32) as usize];
+            }
+            7 => {
+                let sets = PieceSet::ALL;
+                let index = sets.iter().position(|set| *set == self.settings.pieces()).unwrap_or(0) as i32;
+                let mode = self.settings.mode.index();
+                self.settings.pieces[mode] = sets[(index + step).rem_euclid(sets.len() as i32) as usize];
+            }
+            // every leaderboard keeps the same amount, lowering it removes the scores that no longer fit
+            8 => {
+                let index = HIGHSCORE_CAPACITIES.iter().position(|capacity| *capacity == self.high_scores[0].capacity()).unwrap_or(0) as i32;
+                let capacity = HIGHSCORE_CAPACITIES[(index + step).rem_euclid(HIGHSCORE_CAPACITIES.len() as i32) as usize];
+                for high_scores in &mut self.high_scores {
+                    high_scores.set_capacity(capacity);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Updates the menu after a key press
+    ///
+    /// Arguments:
+    ///
+    /// event: MenuEvent - The key which was pressed
+    ///
+    /// Return:
+    ///
+    /// Result<Option<Choice>, TetrisError> - Some if the player wants to leave the menu, otherwise None. An error if the settings could not be saved
+    fn handle(&mut self, event: MenuEvent) -> Result<Option<Choice>, TetrisError> {
+        if self.screen == Screen::Name {
+            match event {
+                MenuEvent::Char(char) if !char.is_control() && self.name.chars().count() < NAME_LENGTH => {
+                    self.name.push(char)
+                }
+                MenuEvent::Erase => {
+                    self.name.pop();
+                }
+                MenuEvent::Select if !self.name.trim().is_empty() => {
+                    return Ok(Some(Choice::Play(User::add_user(self.name.trim().to_string()))));
+                }
+                MenuEvent::Back => self.back(),
+                _ => (),
+            }
+            return Ok(None);
         }
+
+        let count = self.items().len();
+        match event {
+            MenuEvent::Up | MenuEvent::Char('w') => self.selected = (self.selected + count - 1) % count,
+            MenuEvent::Down | MenuEvent::Char('s') => self.selected = (self.selected + 1) % count,
+            MenuEvent::Left | MenuEvent::Char('a') if self.screen == Screen::Settings => self.change_setting(-1),
+            MenuEvent::Right | MenuEvent::Char('d') if self.screen == Screen::Settings => self.change_setting(1),
+            MenuEvent::Back | MenuEvent::Char('q') if self.screen != Screen::Main => self.back(),
+            MenuEvent::Select => match self.screen {
+                Screen::Main => match (self.selected + self.main_offset()).checked_sub(1) {
+                    None => return Ok(Some(Choice::Continue)),
+                    Some(index) => match Screen::MAIN.get(index) {
+                        Some(screen) => self.open(*screen),
+                        None => return Ok(Some(Choice::Quit)),
+                    },
+                },
+                Screen::Modes => {
+                    if let Some(mode) = Mode::ALL.get(self.selected) {
+                        self.settings.mode = *mode;
+                        Settings::save_json(&self.settings, &self.high_scores)?;
+                    }
+                    self.back();
+                }
+                Screen::Settings if self.selected + 1 == count => {
+                    Settings::save_json(&self.settings, &self.high_scores)?;
+                    self.back();
+                }
+                Screen::Settings => self.change_setting(1),
+                _ => self.back(),
+            },
+            _ => (),
+        }
+        Ok(None)
     }
 }
 
 impl Settings {
     /// Intlize the start menu
     ///
-    /// A menu is started which lets you change the settings, view the highscore and start the game
-    /// The setting menu is made of a sub menu of the main menu, the menu takes input by just pressing a letter or number.
+    /// A full screen menu is drawn on the playfield which lets you choose a mode, change the settings,
+    /// view the controls and the highscore and start the game. The menu is navigated with the arrow keys and Enter.
     /// Before the game starts, the user will have to input a name of the user and before the the game starts
     /// the settings is saved to the json file.
     ///
+    /// Arguments:
+    ///
+    /// output: &mut Output<R> - The output which the menu is drawn to
+    /// input: &Input - The input which the key presses are read from
+    ///
     /// Return:
     ///
-    /// crossterm::Result<(Settings, User)> - A crossterm::Result which holds a tuple of an instance of Settings and User
+    /// Result<Option<(Settings, Play)>, TetrisError> - A tuple of an instance of Settings and how the game starts, or None if the user chose to quit
     /// 
     /// Example:
     /// 
-    /// start()
-    /// # => (Settings{color: 'c', difficulty: 5}, User{name: "Tore", score: 50})
-    pub fn start() -> crossterm::Result<(Settings, User)> {
-        let mut result = -1;
-        let (high_scores, mut settings) = Self::load_json();
+    /// start(&mut output, &input)
+    /// # => Some((Settings{theme: "classic", difficulty: 5, mode: Mode::Marathon, animations: AnimationSpeed::Normal, frame_cap: 60, entry_delay: 500, line_clear_delay: 400, randomizers: [RandomizerKind::SevenBag; 3], pieces: [PieceSet::Classic; 3]}, Play::New(User{name: "Tore", score: 0})))
+    pub fn start<R: Renderer>(output: &mut Output<R>, input: &Input) -> Result<Option<(Settings, Play)>, TetrisError> {
+        let (high_scores, settings) = Self::load_json()?;
+        let mut menu = Menu {
+            screen: Screen::Main,
+            selected: 0,
+            name: String::new(),
+            settings,
+            high_scores,
+            themes: Theme::load_all()?,
+            saved: SavedGame::exists(),
+            notice: None,
+        };
         loop {
-            if result == -1 {
-                disable_raw_mode().unwrap();
-                println!(
-                    "Welcome to Tetris\n\nPress 1. to play\nPress 2. for settings\nPress 3. for highscore"
-                );
-                enable_raw_mode().unwrap();
-                result = loop {
-                    if let Event::Key(key) = read().unwrap() {
-                        if key.kind == KeyEventKind::Press {
-                            match key.code {
-                                KeyCode::Char('1') => break 1,
-                                KeyCode::Char('2') => break 2,
-                                KeyCode::Char('3') => break 3,
-                                _ => (),
-                            }
-                        }
-                    }
-                }
+            output.set_theme(menu.theme());
+            output.draw_screen(&menu.lines(), menu.preview())?;
+            output.flush()?;
+            let event = input.menu_event()?;
+            if let MenuEvent::Resize(width, height) = event {
+                output.resize(width, height)?;
             }
-            disable_raw_mode().unwrap();
-            if result == 1 {
-                print!("Input user_name: ");
-                let mut name = String::new();
-                io::stdout().flush().unwrap();
-                io::stdin()
-                    .read_line(&mut name)
-                    .expect("failed to readline");
-                name.pop();
-                let user = User::add_user(name);
-                Self::save_json(settings, high_scores);
-                return Ok((settings.clone(), user));
-            } else if result == 2 {
-                let mut settings_result = -1;
-                loop {
-                    if settings_result == -1 {
-                        println!("Settings\n\nPress 1. for difficulty\nPress 2. for color\nPress 3. for exit");
-                        settings_result = 0;
-                    } else if settings_result == 0 {
-                        enable_raw_mode().unwrap();
-                        settings_result = loop {
-                            if let Event::Key(key) = read().unwrap() {
-                                if key.kind == KeyEventKind::Press {
-                                    match key.code {
-                                        KeyCode::Char('1') => break 1,
-                                        KeyCode::Char('2') => break 2,
-                                        KeyCode::Char('3') => break 3,
-                                        _ => (),
-                                    }
-                                }
-                            }
-                        };
-                        if settings_result == 3 {
-                            break;
-                        }
-                        disable_raw_mode().unwrap();
-                    }
-                    if settings_result == 1 {
-                        println!("Set difficulty in the range 1-9");
-                        enable_raw_mode().unwrap();
-                        let difficulty = loop {
-                            if let Event::Key(key) = read().unwrap() {
-                                if key.kind == KeyEventKind::Press {
-                                    match key.code {
-                                        KeyCode::Char(event) => {
-                                            if event.is_digit(10) {
-                                                break event.to_digit(10).unwrap();
-                                            }else{
-                                                disable_raw_mode().unwrap();
-                                                println!("You need to enter a number in the range 1-9");
-                                                enable_raw_mode().unwrap();
-                                            }
-                                        }
-                                        _ => (),
-                                    }
-                                }
-                            }
-                        };
-                        settings_result = -1;
-                        disable_raw_mode().unwrap();
-                        settings.difficulty = difficulty;
-                    }
-                    if settings_result == 2 {
-                        println!("Set color\n\nPress r. for red\nPress b. for black");
-                        enable_raw_mode().unwrap();
-                        let color = loop {
-                            if let Event::Key(key) = read().unwrap() {
-                                match key.code {
-                                    KeyCode::Char('r') => break 'r',
-                                    KeyCode::Char('b') => break 'b',
-                                    KeyCode::Char(event) => {
-                                        disable_raw_mode().unwrap();
-                                        println!("You need to enter the character r or b");
-                                        enable_raw_mode().unwrap();
-                                    },
-                                    _ => (),
-                                }
-                            }
-                        };
-                        settings_result = -1;
-                        disable_raw_mode().unwrap();
-                        settings.color = color
-                    }
-                }
-                result = -1;
-                disable_raw_mode().unwrap();
-            } else if result == 3 {
-                println!("\nHighscore:");
-                for x in high_scores.users.iter() {
-                    println!("{}: {}", x.name, x.score)
+            match menu.handle(event)? {
+                Some(Choice::Play(user)) => {
+                    Self::save_json(&menu.settings, &menu.high_scores)?;
+                    return Ok(Some((menu.settings, Play::New(user))));
                 }
-                println!("\nPress any key to exit:");
-                enable_raw_mode().unwrap();
-                result = loop {
-                    if let Event::Key(key) = read().unwrap() {
-                        if key.kind == KeyEventKind::Press {
-                            match key.code {
-                                KeyCode::Char(_event) => break -1,
-                                _ => (),
-                            }
-                        }
+                Some(Choice::Continue) => match SavedGame::load() {
+                    Ok(saved) => return Ok(Some((menu.settings, Play::Continue(Box::new(saved))))),
+                    // a save that can't be read would fail every time, so it is removed and the menu goes on without it
+                    Err(_) => {
+                        SavedGame::remove()?;
+                        menu.saved = false;
+                        menu.notice = Some("The saved game was broken and removed".to_string());
                     }
-                };
-                disable_raw_mode().unwrap();
+                },
+                Some(Choice::Quit) => return Ok(None),
+                None => (),
             }
         }
     }
 
+    /// A getter for the difficulty that was chosen, it is used as the starting level
+    pub fn difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    /// A getter for the game mode that was chosen
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// A getter for the name of the theme that was chosen
+    pub fn theme(&self) -> &str {
+        &self.theme
+    }
+
+    /// A getter for the animation speed that was chosen
+    pub fn animations(&self) -> AnimationSpeed {
+        self.animations
+    }
+
+    /// The shortest time between two frames, from the frame cap that was chosen
+    ///
+    /// Example:
+    ///
+    /// Settings{frame_cap: 60, ..}.frame_time()
+    /// # => 16.666ms
+    pub fn frame_time(&self) -> Duration {
+        Duration::from_secs(1) / self.frame_cap.max(1)
+    }
+
+    /// The time between a piece locking and the next one spawning, after the cleared lines are removed if there were any
+    ///
+    /// Example:
+    ///
+    /// Settings{entry_delay: 300, ..}.entry_delay()
+    /// # => 300ms
+    pub fn entry_delay(&self) -> Duration {
+        Duration::from_millis(self.entry_delay as u64)
+    }
+
+    /// The time cleared lines stay on the board before they are removed, the line clear animation is played during it
+    ///
+    /// Example:
+    ///
+    /// Settings{line_clear_delay: 0, ..}.line_clear_delay()
+    /// # => 0ns
+    pub fn line_clear_delay(&self) -> Duration {
+        Duration::from_millis(self.line_clear_delay as u64)
+    }
+
+    /// The randomizer that was chosen for the mode that is played
+    ///
+    /// Example:
+    ///
+    /// Settings{mode: Mode::Sprint, randomizers: [RandomizerKind::SevenBag, RandomizerKind::Nes, RandomizerKind::SevenBag], ..}.randomizer()
+    /// # => RandomizerKind::Nes
+    pub fn randomizer(&self) -> RandomizerKind {
+        self.randomizers[self.mode.index()]
+    }
+
+    /// The piece set that was chosen for the mode that is played
+    ///
+    /// Example:
+    ///
+    /// Settings{mode: Mode::Marathon, pieces: [PieceSet::Pentominoes, PieceSet::Classic, PieceSet::Classic], ..}.pieces()
+    /// # => PieceSet::Pentominoes
+    pub fn pieces(&self) -> PieceSet {
+        self.pieces[self.mode.index()]
+    }
+
     /// Load the local settings.json file for highscore and settings
     ///
-    /// The json file is loaded or if it doesnt exsist an error is raised.
-    /// The json is parsed and then placed into a Highscore instant and a instant of settings
-    /// Which is then returned
+    /// The json file is loaded or if it doesnt exsist an error is returned.
+    /// The json is parsed and then placed into a Highscore instant for every mode and a instant of settings
+    /// Which is then returned. Files from older versions which only store a color character
+    /// get the matching theme, 'r' is the red theme and 'b' is the black theme. Their single
+    /// highscore list becomes the Marathon leaderboard.
     ///
     /// Return:
     ///
-    /// (Highscores, Self) - A tuple of an instance of Highscore and an instance of Settings
+    /// Result<([Highscores; 3], Self), TetrisError> - A tuple of the leaderboards in the order of Mode::ALL and an instance of Settings,
+    /// or an error if the file can't be read, isn't json or is missing the difficulty or a score
     /// 
     /// Example:
     /// 
     /// load_json()
-    /// # => (Highscore{users: [User{name: "Tore", score: 50}, User{name: "Carl", score: 20}]}, Settings{difficulty: 4, color: 'b'})
-    pub fn load_json() -> (Highscores, Self) {
-        let content = fs::read_to_string("./src/settings.json").expect("error");
-        let json = json::parse(&content).unwrap();
+    /// # => ([Highscore{users: [User{name: "Tore", score: 50}, User{name: "Carl", score: 20}], capacity: 10, lowest_first: false}, ..], Settings{difficulty: 4, theme: "black", mode: Mode::Marathon, animations: AnimationSpeed::Normal, frame_cap: 60, entry_delay: 500, line_clear_delay: 400, randomizers: [RandomizerKind::SevenBag; 3], pieces: [PieceSet::Classic; 3]})
+    pub fn load_json() -> Result<([Highscores; 3], Self), TetrisError> {
+        let path = data_path(SETTINGS_FILE);
+        let content = fs::read_to_string(&path).map_err(|error| TetrisError::Io(path.clone(), error))?;
+        let json = json::parse(&content).map_err(|error| TetrisError::Parse(path, error))?;
         let settings = Settings {
-            difficulty: json["difficulty"].as_u32().unwrap(),
-            color: json["color"].as_str().unwrap().chars().nth(0).unwrap(),
+            difficulty: json["difficulty"]
+                .as_u32()
+                .ok_or_else(|| TetrisError::Config("difficulty is missing or not a number".to_string()))?,
+            theme: match (json["theme"].as_str(), json["color"].as_str()) {
+                (Some(theme), _) => theme.to_string(),
+                (None, Some("r")) => "red".to_string(),
+                (None, Some("b")) => "black".to_string(),
+                _ => Theme::default().name,
+            },
+            mode: json["mode"].as_str().and_then(Mode::parse).unwrap_or(Mode::Marathon),
+            animations: json["animations"].as_str().and_then(AnimationSpeed::parse).unwrap_or(AnimationSpeed::Normal),
+            frame_cap: json["frame_cap"].as_u32().unwrap_or(DEFAULT_FRAME_CAP),
+            entry_delay: json["entry_delay"].as_u32().unwrap_or(DEFAULT_ENTRY_DELAY),
+            line_clear_delay: json["line_clear_delay"].as_u32().unwrap_or(DEFAULT_LINE_CLEAR_DELAY),
+            randomizers: Mode::ALL.map(|mode| {
+                json["randomizers"][mode.name()]
+                    .as_str()
+                    .and_then(RandomizerKind::parse)
+                    .unwrap_or(RandomizerKind::SevenBag)
+            }),
+            pieces: Mode::ALL.map(|mode| {
+                json["pieces"][mode.name()]
+                    .as_str()
+                    .and_then(PieceSet::parse)
+                    .unwrap_or(PieceSet::Classic)
+            }),
         };
-        let mut high_scores = Highscores { users: vec![] };
-        for x in json["highscore"].members() {
-            high_scores.users.push(User {
-                name: x["name"].to_string(),
-                score: x["score"].as_u32().unwrap(),
-            })
+        let capacity = json["highscore_capacity"]
+            .as_usize()
+            .unwrap_or(DEFAULT_HIGHSCORE_CAPACITY);
+        let mut high_scores = Mode::ALL.map(|mode| Highscores::new(capacity, mode.lowest_first()));
+        for mode in Mode::ALL {
+            let users = match (&json["highscores"][mode.name()], mode) {
+                (users, _) if users.is_array() => users,
+                (_, Mode::Marathon) => &json["highscore"],
+                _ => continue,
+            };
+            for x in users.members() {
+                high_scores[mode.index()].insert(User {
+                    name: x["name"].to_string(),
+                    score: x["score"]
+                        .as_u32()
+                        .ok_or_else(|| TetrisError::Config(format!("the highscore of {} is not a number", x["name"])))?,
+                });
+            }
         }
-        return (high_scores, settings);
+        Ok((high_scores, settings))
     }
 
     /// Save memory to the local settings.json file that stores highscore and settings
@@ -292,23 +910,155 @@ impl Settings {
     /// 
     /// Arguments:
     ///
-    /// settings: Settings - An instance of Settings which holds the settings
-    /// high_scores: Highscores - An instance of Highscore which holds the highscore
+    /// settings: &Settings - An instance of Settings which holds the settings
+    /// high_scores: &[Highscores; 3] - The leaderboards of every mode, in the order of Mode::ALL
+    ///
+    /// Return:
+    ///
+    /// Result<(), TetrisError> - An error if the file couldn't be written
     /// 
     /// Example:
     /// 
-    /// save_json(Highscore{users: [User{name: "Tore", score: 50}, User{name: "Carl", score: 20}]}, Settings{difficulty: 4, color: 'b'})
-    pub fn save_json(settings: Self, high_scores: Highscores) {
+    /// save_json([Highscore{users: [User{name: "Tore", score: 50}, User{name: "Carl", score: 20}], capacity: 10, lowest_first: false}, ..], Settings{difficulty: 4, theme: "black", mode: Mode::Marathon, animations: AnimationSpeed::Normal, frame_cap: 60, entry_delay: 500, line_clear_delay: 400, randomizers: [RandomizerKind::SevenBag; 3], pieces: [PieceSet::Classic; 3]})
+    pub fn save_json(settings: &Self, high_scores: &[Highscores; 3]) -> Result<(), TetrisError> {
         let mut data = object! {
             difficulty: settings.difficulty,
-            color: settings.color.to_string()
+            theme: settings.theme.as_str(),
+            mode: settings.mode.name(),
+            animations: settings.animations.name(),
+            frame_cap: settings.frame_cap,
+            entry_delay: settings.entry_delay,
+            line_clear_delay: settings.line_clear_delay,
+            highscore_capacity: high_scores[0].capacity()
         };
-        data["highscore"] = json::JsonValue::new_array();
-        for x in high_scores.users {
-            data["highscore"]
-                .push(object! {name: x.name, score: x.score})
-                .expect("Error occured while reading highscore");
+        for mode in Mode::ALL {
+            data["randomizers"][mode.name()] = settings.randomizers[mode.index()].name().into();
+            data["pieces"][mode.name()] = settings.pieces[mode.index()].name().into();
+            data["highscores"][mode.name()] = json::JsonValue::Array(
+                high_scores[mode.index()]
+                    .users
+                    .iter()
+                    .map(|x| object! {name: x.name.as_str(), score: x.score})
+                    .collect(),
+            );
         }
-        fs::write("./src/settings.json", data.dump()).expect("Unable to write file");
+        let path = data_path(SETTINGS_FILE);
+        fs::write(&path, data.dump()).map_err(|error| TetrisError::Io(path, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, score: u32) -> User {
+        User { name: name.to_string(), score }
+    }
+
+    fn ranking(high_scores: &Highscores) -> Vec<(&str, u32)> {
+        high_scores.users().iter().map(|user| (user.name(), user.score)).collect()
+    }
+
+    #[test]
+    fn insert_orders_by_biggest_score() {
+        let mut high_scores = Highscores::new(10, false);
+        assert_eq!(high_scores.insert(user("Carl", 20)), Some(1));
+        assert_eq!(high_scores.insert(user("Tore", 50)), Some(1));
+        assert_eq!(high_scores.insert(user("Jane", 30)), Some(2));
+        assert_eq!(ranking(&high_scores), vec![("Tore", 50), ("Jane", 30), ("Carl", 20)]);
+    }
+
+    #[test]
+    fn insert_ranks_a_tie_after_the_earlier_score() {
+        let mut high_scores = Highscores::new(10, false);
+        high_scores.insert(user("Carl", 20));
+        high_scores.insert(user("Tore", 50));
+        assert_eq!(high_scores.insert(user("Jane", 20)), Some(3));
+        assert_eq!(high_scores.insert(user("John", 50)), Some(2));
+        assert_eq!(ranking(&high_scores), vec![("Tore", 50), ("John", 50), ("Carl", 20), ("Jane", 20)]);
+    }
+
+    #[test]
+    fn insert_keeps_the_capacity() {
+        let mut high_scores = Highscores::new(2, false);
+        high_scores.insert(user("Carl", 20));
+        high_scores.insert(user("Tore", 50));
+        assert_eq!(high_scores.insert(user("Jane", 30)), Some(2));
+        assert_eq!(ranking(&high_scores), vec![("Tore", 50), ("Jane", 30)]);
+        // a score that doesn't beat the last one doesn't place, not even on a tie
+        assert_eq!(high_scores.insert(user("John", 30)), None);
+        assert_eq!(high_scores.insert(user("Anna", 10)), None);
+        assert_eq!(ranking(&high_scores), vec![("Tore", 50), ("Jane", 30)]);
+    }
+
+    #[test]
+    fn insert_with_no_capacity_never_places() {
+        let mut high_scores = Highscores::new(0, false);
+        assert_eq!(high_scores.insert(user("Tore", 50)), None);
+        assert!(high_scores.users().is_empty());
+    }
+
+    #[test]
+    fn set_capacity_removes_the_lowest_users() {
+        let mut high_scores = Highscores::new(10, false);
+        for (name, score) in [("Carl", 20), ("Tore", 50), ("Jane", 30)] {
+            high_scores.insert(user(name, score));
+        }
+        high_scores.set_capacity(1);
+        assert_eq!(ranking(&high_scores), vec![("Tore", 50)]);
+    }
+
+    #[test]
+    fn insert_lowest_first_ranks_the_fastest_time_first() {
+        let mut high_scores = Highscores::new(2, true);
+        assert_eq!(high_scores.insert(user("Carl", 90000)), Some(1));
+        assert_eq!(high_scores.insert(user("Tore", 62500)), Some(1));
+        assert_eq!(high_scores.insert(user("Jane", 62500)), Some(2));
+        assert_eq!(high_scores.insert(user("John", 95000)), None);
+        assert_eq!(ranking(&high_scores), vec![("Tore", 62500), ("Jane", 62500)]);
+    }
+
+    #[test]
+    fn highscore_capacity_is_changed_for_every_mode_in_the_settings() {
+        let mut menu = Menu {
+            screen: Screen::Settings,
+            selected: 8,
+            name: String::new(),
+            settings: Settings {
+                difficulty: 1,
+                theme: Theme::default().name,
+                mode: Mode::Sprint,
+                animations: AnimationSpeed::Normal,
+                frame_cap: DEFAULT_FRAME_CAP,
+                entry_delay: DEFAULT_ENTRY_DELAY,
+                line_clear_delay: DEFAULT_LINE_CLEAR_DELAY,
+                randomizers: [RandomizerKind::SevenBag; 3],
+                pieces: [PieceSet::Classic; 3],
+            },
+            high_scores: Mode::ALL.map(|mode| Highscores::new(DEFAULT_HIGHSCORE_CAPACITY, mode.lowest_first())),
+            themes: vec![Theme::default()],
+            saved: false,
+            notice: None,
+        };
+        for (name, score) in [("Carl", 20), ("Tore", 50), ("Jane", 30), ("John", 40), ("Anna", 10), ("Erik", 60)] {
+            menu.high_scores[0].insert(user(name, score));
+        }
+        assert_eq!(menu.items()[8], "Highscores kept  < 10 >");
+
+        menu.change_setting(-1);
+        assert_eq!(menu.items()[8], "Highscores kept  < 5 >");
+        assert!(menu.high_scores.iter().all(|high_scores| high_scores.capacity() == 5));
+        assert_eq!(menu.high_scores[0].users().len(), 5);
+
+        // it wraps around like the other settings
+        menu.change_setting(-1);
+        assert_eq!(menu.items()[8], "Highscores kept  < 100 >");
+    }
+
+    #[test]
+    fn sprint_scores_are_shown_as_times() {
+        assert_eq!(Mode::Sprint.format_score(62500), "1:02.50");
+        assert_eq!(Mode::Sprint.format_score(9990), "0:09.99");
+        assert_eq!(Mode::Marathon.format_score(400), "400");
     }
 }
@@ -0,0 +1,132 @@
+use std::fs;
+
+use crate::*;
+
+/// The file in the data directory the custom piece set is read from when a game starts
+const CUSTOM_PIECES: &str = "pieces/custom.txt";
+
+/// The piece sets that can be chosen in the settings
+///
+/// Every set is a text file in the pieces directory with the shape, color and rotation data of its pieces, see Shape::parse_shapes.
+/// The built in sets are baked into the program, the custom set is read from its file every time a game starts so it can be changed without building again.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PieceSet {
+    Classic,
+    Pentominoes,
+    Chaos,
+    Custom,
+}
+
+impl PieceSet {
+    /// All piece sets in the order they are shown in the menu
+    pub const ALL: [PieceSet; 4] = [
+        PieceSet::Classic,
+        PieceSet::Pentominoes,
+        PieceSet::Chaos,
+        PieceSet::Custom,
+    ];
+
+    /// The name of the piece set as it is shown in the menu and saved in settings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            PieceSet::Classic => "Classic",
+            PieceSet::Pentominoes => "Pentominoes",
+            PieceSet::Chaos => "Chaos",
+            PieceSet::Custom => "Custom",
+        }
+    }
+
+    /// Finds the piece set with the given name
+    ///
+    /// Example:
+    ///
+    /// PieceSet::parse("chaos")
+    /// # => Some(PieceSet::Chaos)
+    pub fn parse(name: &str) -> Option<PieceSet> {
+        Self::ALL
+            .into_iter()
+            .find(|set| set.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Loads the pieces of the set
+    ///
+    /// Return:
+    ///
+    /// Result<Vec<Shape>, TetrisError> - The pieces, or an error if the custom file can't be read or has mistakes in it
+    ///
+    /// Example:
+    ///
+    /// PieceSet::Classic.shapes()
+    /// # => Ok([Shape { name: 'I', .. }, Shape { name: 'J', .. }, ..])
+    pub fn shapes(&self) -> Result<Vec<Shape>, TetrisError> {
+        let text = match self {
+            PieceSet::Classic => include_str!("pieces/classic.txt").to_string(),
+            PieceSet::Pentominoes => include_str!("pieces/pentominoes.txt").to_string(),
+            PieceSet::Chaos => include_str!("pieces/chaos.txt").to_string(),
+            PieceSet::Custom => {
+                let path = data_path(CUSTOM_PIECES);
+                fs::read_to_string(&path).map_err(|error| TetrisError::Io(path, error))?
+            }
+        };
+        self.parse_shapes(&text)
+    }
+
+    /// Reads the pieces of the set from its text, a set without any pieces can't be played
+    fn parse_shapes(&self, text: &str) -> Result<Vec<Shape>, TetrisError> {
+        let source = format!("{} pieces", self.name());
+        let shapes = Shape::parse_shapes(text, &source)?;
+        if shapes.is_empty() {
+            return Err(TetrisError::Data(source, "there are no pieces".to_string()));
+        }
+        Ok(shapes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(shapes: &[Shape]) -> String {
+        shapes.iter().map(Shape::name).collect()
+    }
+
+    #[test]
+    fn every_piece_set_loads() {
+        for set in PieceSet::ALL {
+            assert!(!set.shapes().unwrap().is_empty(), "{}", set.name());
+        }
+    }
+
+    #[test]
+    fn classic_has_the_seven_tetrominoes() {
+        let shapes = PieceSet::Classic.shapes().unwrap();
+        assert_eq!(names(&shapes), "IJLOSTZ");
+        assert!(shapes.iter().all(|shape| shape.extent().len() == 4));
+        // the custom set starts out as a copy of the classic set
+        assert_eq!(names(&PieceSet::Custom.shapes().unwrap()), "IJLOSTZ");
+    }
+
+    #[test]
+    fn pentominoes_has_eighteen_pieces_of_five_blocks() {
+        let shapes = PieceSet::Pentominoes.shapes().unwrap();
+        assert_eq!(shapes.len(), 18);
+        assert!(shapes.iter().all(|shape| shape.extent().len() == 5));
+    }
+
+    #[test]
+    fn broken_sets_say_which_set_and_what_is_wrong() {
+        let message = |text: &str| match PieceSet::Custom.parse_shapes(text) {
+            Err(TetrisError::Data(source, message)) => {
+                assert_eq!(source, "Custom pieces");
+                message
+            }
+            _ => panic!("expected a data error"),
+        };
+        assert_eq!(message("// only a comment"), "there are no pieces");
+        assert_eq!(message("T 2 srs\n.#.\n###"), "line 3: the box of T has 2 rows but should have 3");
+        assert_eq!(message("T 2 spin\n.#.\n###\n..."), "line 1: the kicks of T should be srs, i or none");
+        assert_eq!(message("T two srs\n.#.\n###\n..."), "line 1: the color of T is not a number");
+        assert_eq!(message("Tee 2 srs\n.#.\n###\n..."), "line 1: the name of a piece should be one character, not Tee");
+        assert_eq!(message("T 2 srs\n...\n...\n..."), "line 1: T has no blocks");
+    }
+}
@@ -0,0 +1,386 @@
+use crate::*;
+
+/// The random number generator of a game
+///
+/// Every random choice in a game comes from here, so a game started with the same seed gets the same pieces.
+/// It is a small xorshift generator, so that its state is a single number which is easy to store.
+#[derive(Clone, Debug)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    /// Creates a generator from a seed, the same seed always gives the same numbers
+    ///
+    /// Arguments:
+    ///
+    /// seed: u64 - Any number, 0 is replaced since xorshift can't start from it
+    ///
+    /// Example:
+    ///
+    /// GameRng::new(42)
+    /// # => GameRng{state: 42}
+    pub fn new(seed: u64) -> Self {
+        Self { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    /// Creates a generator with a random seed
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// The current state of the generator, GameRng::new(rng.state()) continues with the same numbers
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Gives a number between 0 and below, below itself is not included
+    ///
+    /// Example:
+    ///
+    /// GameRng::new(42).below(7)
+    /// # => one of 0, 1, 2, 3, 4, 5 and 6
+    pub fn below(&mut self, below: usize) -> usize {
+        (self.next_u64() % below.max(1) as u64) as usize
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Decides which piece comes next
+///
+/// The randomizer only knows how many pieces there are, it answers with an index among them.
+/// All randomness is taken from the game's GameRng.
+pub trait Randomizer {
+    /// Picks the next piece
+    ///
+    /// Arguments:
+    ///
+    /// rng: &mut GameRng - The random number generator of the game
+    /// pieces: usize - How many different pieces there are
+    ///
+    /// Return:
+    ///
+    /// usize - The index of the piece, below pieces
+    fn next(&mut self, rng: &mut GameRng, pieces: usize) -> usize;
+
+    /// What the randomizer remembers, so that it can be saved with the game
+    ///
+    /// Example:
+    ///
+    /// Bag::new(1).state()
+    /// # => [] (a new bag is filled on the next piece)
+    fn state(&self) -> Vec<usize>;
+
+    /// Sets what the randomizer remembers to a state from state()
+    fn restore(&mut self, state: &[usize]);
+}
+
+/// Puts a number of copies of every piece in a bag and hands them out in a random order. A new bag is filled when it runs out.
+///
+/// With the seven standard pieces one copy is a 7-bag and two copies is a 14-bag.
+pub struct Bag {
+    copies: usize,
+    bag: Vec<usize>,
+}
+
+impl Bag {
+    /// Creates an empty bag that is filled with copies of every piece
+    pub fn new(copies: usize) -> Self {
+        Self { copies, bag: Vec::new() }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut GameRng, pieces: usize) -> usize {
+        if self.bag.is_empty() {
+            self.bag = (0..pieces).flat_map(|piece| std::iter::repeat_n(piece, self.copies.max(1))).collect();
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap_or(0)
+    }
+
+    fn state(&self) -> Vec<usize> {
+        self.bag.clone()
+    }
+
+    fn restore(&mut self, state: &[usize]) {
+        self.bag = state.to_vec();
+    }
+}
+
+/// Every piece is as likely every time, no matter what came before
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+    fn next(&mut self, rng: &mut GameRng, pieces: usize) -> usize {
+        rng.below(pieces)
+    }
+
+    fn state(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    fn restore(&mut self, _state: &[usize]) {}
+}
+
+/// Remembers the last four pieces and rolls up to four times for a piece that isn't one of them, like the first Tetris: The Grand Master.
+/// If every roll hits the history the last roll is used anyway.
+pub struct History {
+    history: Vec<usize>,
+}
+
+/// How many pieces History remembers and how many times it rolls
+const HISTORY_LENGTH: usize = 4;
+const HISTORY_ROLLS: usize = 4;
+
+impl History {
+    /// Creates a randomizer with an empty history
+    pub fn new() -> Self {
+        Self { history: Vec::new() }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self, rng: &mut GameRng, pieces: usize) -> usize {
+        let mut piece = rng.below(pieces);
+        for _ in 1..HISTORY_ROLLS {
+            if !self.history.contains(&piece) {
+                break;
+            }
+            piece = rng.below(pieces);
+        }
+        self.history.push(piece);
+        if self.history.len() > HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+        piece
+    }
+
+    fn state(&self) -> Vec<usize> {
+        self.history.clone()
+    }
+
+    fn restore(&mut self, state: &[usize]) {
+        self.history = state.iter().rev().take(HISTORY_LENGTH).rev().copied().collect();
+    }
+}
+
+/// Rolls among the pieces and one extra value, like the NES version. If the roll is the extra value or the same piece as last time it rolls once more, and that roll is kept.
+pub struct Reroll {
+    last: Option<usize>,
+}
+
+impl Reroll {
+    /// Creates a randomizer which hasn't handed out any piece yet
+    pub fn new() -> Self {
+        Self { last: None }
+    }
+}
+
+impl Default for Reroll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Randomizer for Reroll {
+    fn next(&mut self, rng: &mut GameRng, pieces: usize) -> usize {
+        let mut piece = rng.below(pieces + 1);
+        if piece == pieces || Some(piece) == self.last {
+            piece = rng.below(pieces);
+        }
+        self.last = Some(piece);
+        piece
+    }
+
+    fn state(&self) -> Vec<usize> {
+        self.last.into_iter().collect()
+    }
+
+    fn restore(&mut self, state: &[usize]) {
+        self.last = state.first().copied();
+    }
+}
+
+/// The randomizers that can be chosen in the menu
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RandomizerKind {
+    SevenBag,
+    FourteenBag,
+    Random,
+    Tgm,
+    Nes,
+}
+
+impl RandomizerKind {
+    /// All randomizers in the order they are shown in the menu
+    pub const ALL: [RandomizerKind; 5] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::Random,
+        RandomizerKind::Tgm,
+        RandomizerKind::Nes,
+    ];
+
+    /// The name of the randomizer as it is shown in the menu and saved in settings.json
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7-bag",
+            RandomizerKind::FourteenBag => "14-bag",
+            RandomizerKind::Random => "Random",
+            RandomizerKind::Tgm => "TGM",
+            RandomizerKind::Nes => "NES",
+        }
+    }
+
+    /// Finds the randomizer with the given name
+    ///
+    /// Example:
+    ///
+    /// RandomizerKind::parse("nes")
+    /// # => Some(RandomizerKind::Nes)
+    pub fn parse(name: &str) -> Option<RandomizerKind> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Creates a new randomizer of this kind
+    pub fn create(&self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::Random => Box::new(Uniform),
+            RandomizerKind::Tgm => Box::new(History::new()),
+            RandomizerKind::Nes => Box::new(Reroll::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(randomizer: &mut dyn Randomizer, seed: u64, count: usize) -> Vec<usize> {
+        let mut rng = GameRng::new(seed);
+        (0..count).map(|_| randomizer.next(&mut rng, 7)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_pieces() {
+        for kind in RandomizerKind::ALL {
+            let first = pieces(kind.create().as_mut(), 42, 50);
+            let second = pieces(kind.create().as_mut(), 42, 50);
+            assert_eq!(first, second, "{}", kind.name());
+            assert!(first.iter().all(|&piece| piece < 7), "{}", kind.name());
+        }
+        assert_ne!(pieces(&mut Uniform, 1, 50), pieces(&mut Uniform, 2, 50));
+    }
+
+    #[test]
+    fn rng_continues_from_its_state() {
+        let mut rng = GameRng::new(7);
+        rng.next_u64();
+        let mut copy = GameRng::new(rng.state());
+        assert_eq!((0..10).map(|_| rng.below(100)).collect::<Vec<_>>(), (0..10).map(|_| copy.below(100)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn zero_seed_still_gives_numbers() {
+        let mut rng = GameRng::new(0);
+        assert_ne!(rng.next_u64(), 0);
+    }
+
+    #[test]
+    fn seven_bag_gives_every_piece_once_per_bag() {
+        let pieces = pieces(&mut Bag::new(1), 3, 70);
+        for bag in pieces.chunks(7) {
+            let mut bag = bag.to_vec();
+            bag.sort();
+            assert_eq!(bag, (0..7).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn fourteen_bag_gives_every_piece_twice_per_bag() {
+        let pieces = pieces(&mut Bag::new(2), 3, 70);
+        for bag in pieces.chunks(14) {
+            for piece in 0..7 {
+                assert_eq!(bag.iter().filter(|&&other| other == piece).count(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn restored_bag_continues_with_the_same_pieces() {
+        let mut rng = GameRng::new(11);
+        let mut bag = Bag::new(1);
+        for _ in 0..3 {
+            bag.next(&mut rng, 7);
+        }
+        let mut restored = Bag::new(1);
+        restored.restore(&bag.state());
+        let mut restored_rng = GameRng::new(rng.state());
+        for _ in 0..20 {
+            assert_eq!(bag.next(&mut rng, 7), restored.next(&mut restored_rng, 7));
+        }
+    }
+
+    #[test]
+    fn history_remembers_the_last_four_pieces() {
+        let mut history = History::new();
+        let pieces = pieces(&mut history, 5, 10);
+        assert_eq!(history.state(), pieces[6..]);
+
+        history.restore(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(history.state(), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn reroll_remembers_the_last_piece() {
+        let mut reroll = Reroll::new();
+        assert_eq!(reroll.state(), Vec::<usize>::new());
+        let pieces = pieces(&mut reroll, 5, 10);
+        assert_eq!(reroll.state(), vec![pieces[9]]);
+    }
+
+    #[test]
+    fn kinds_are_parsed_by_name() {
+        for kind in RandomizerKind::ALL {
+            assert_eq!(RandomizerKind::parse(kind.name()), Some(kind));
+        }
+        assert_eq!(RandomizerKind::parse("tgm"), Some(RandomizerKind::Tgm));
+        assert_eq!(RandomizerKind::parse("8-bag"), None);
+    }
+}
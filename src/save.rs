@@ -0,0 +1,346 @@
+use crate::*;
+use json::{object, JsonValue};
+use std::fs;
+use std::path::Path;
+
+/// The file in the data directory a game is saved to when the player quits in the middle of it
+pub const SAVE_FILE: &str = "save.json";
+
+/// A game that was quit before it ended, and everything needed to continue it exactly where it stopped
+///
+/// Fields:
+/// game: GameState - The board, the player, the queue, hold, score and the state of the randomizer and its generator
+/// user: User - The player, the score is put on the leaderboard when the game ends
+/// mode: Mode - The game mode, a saved game keeps its mode even if another one is chosen in the menu
+/// pieces: PieceSet - The piece set the game is played with
+/// randomizer: RandomizerKind - The kind of randomizer, its state is kept in game
+/// time: Duration - How long the game had been going on, the clock starts from here
+/// fall_timer: Duration - When the player last fell, in the time of the game
+/// respawn_at: Duration - When the next piece spawns if there is no player
+/// clear_at: Duration - When the cleared rows are removed if there are any
+/// ranked: bool - If the score goes on the leaderboard, games started from an imported fumen are practice and don't
+pub struct SavedGame {
+    pub game: GameState,
+    pub user: User,
+    pub mode: Mode,
+    pub pieces: PieceSet,
+    pub randomizer: RandomizerKind,
+    pub time: Duration,
+    pub fall_timer: Duration,
+    pub respawn_at: Duration,
+    pub clear_at: Duration,
+    pub ranked: bool,
+}
+
+impl SavedGame {
+    /// Checks if there is a saved game which can be continued
+    pub fn exists() -> bool {
+        Path::new(&data_path(SAVE_FILE)).exists()
+    }
+
+    /// Writes the game to the save file, a game that was saved before is replaced
+    ///
+    /// A game that has already ended, e.g. because the last piece locked out, is not saved since there is nothing to continue.
+    ///
+    /// Return:
+    ///
+    /// Result<(), TetrisError> - An error if the game is over or the file couldn't be written
+    pub fn save(&self) -> Result<(), TetrisError> {
+        if let Some(reason) = self.game.top_out() {
+            return Err(TetrisError::Save(format!("the game ended with a {}", reason.name())));
+        }
+        let path = data_path(SAVE_FILE);
+        fs::write(&path, self.to_json().dump()).map_err(|error| TetrisError::Io(path, error))
+    }
+
+    /// Reads the saved game and removes the file, so a game can only be continued once. If it is quit again it is saved again.
+    ///
+    /// Return:
+    ///
+    /// Result<SavedGame, TetrisError> - The game, or an error if the file can't be read or doesn't describe a game that can be continued
+    ///
+    /// Example:
+    ///
+    /// SavedGame::load()
+    /// # => Ok(SavedGame{mode: Mode::Sprint, pieces: PieceSet::Classic, time: 42.1s, ..})
+    pub fn load() -> Result<SavedGame, TetrisError> {
+        let path = data_path(SAVE_FILE);
+        let content = fs::read_to_string(&path).map_err(|error| TetrisError::Io(path.clone(), error))?;
+        let json = json::parse(&content).map_err(|error| TetrisError::Parse(path.clone(), error))?;
+        let saved = Self::from_json(&json)?;
+        fs::remove_file(&path).map_err(|error| TetrisError::Io(path, error))?;
+        Ok(saved)
+    }
+
+    /// Removes the saved game, e.g. when it is broken and can't be continued. A game that isn't there is already removed.
+    ///
+    /// Return:
+    ///
+    /// Result<(), TetrisError> - An error if the file is there but couldn't be removed
+    pub fn remove() -> Result<(), TetrisError> {
+        let path = data_path(SAVE_FILE);
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(TetrisError::Io(path, error)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Turns the game into json. The grid is stored as rows of numbers where 0 is empty, -1 is garbage and every other number is the index of a shape plus one.
+    /// The state of the generator is stored as a string, json numbers can't hold every u64.
+    fn to_json(&self) -> JsonValue {
+        let game = &self.game;
+        let millis = |time: Duration| time.as_millis() as u64;
+        object! {
+            user: self.user.name(),
+            mode: self.mode.name(),
+            pieces: self.pieces.name(),
+            randomizer: self.randomizer.name(),
+            time: millis(self.time),
+            fall_timer: millis(self.fall_timer),
+            respawn_at: millis(self.respawn_at),
+            clear_at: millis(self.clear_at),
+            ranked: self.ranked,
+            grid: game.grid.iter().map(|row| {
+                row.iter().map(|cell| match cell {
+                    Cell::Piece(index) => *index as i64 + 1,
+                    Cell::Garbage => -1,
+                    _ => 0,
+                }).collect::<Vec<i64>>()
+            }).collect::<Vec<_>>(),
+            player: game.player.as_ref().map(|player| {
+                let (x, y) = player.position();
+                object! { x: x, y: y, rotation: player.rotation() }
+            }),
+            current: game.current,
+            upcoming: game.upcoming,
+            held: game.held,
+            hold_used: game.hold_used,
+            points: game.points,
+            lines: game.lines,
+            pieces_placed: game.pieces,
+            piece_counts: game.piece_counts.clone(),
+            start_level: game.start_level,
+            clearing: game.clearing.clone(),
+            rng: game.rng.state().to_string(),
+            randomizer_state: game.randomizer.state(),
+        }
+    }
+
+    /// Reads a game from the json that to_json() wrote
+    ///
+    /// Arguments:
+    ///
+    /// json: &JsonValue - The contents of the save file
+    ///
+    /// Return:
+    ///
+    /// Result<SavedGame, TetrisError> - The game, or an error if something is missing or doesn't fit the piece set
+    fn from_json(json: &JsonValue) -> Result<SavedGame, TetrisError> {
+        let missing = |what: &str| TetrisError::Save(format!("{what} is missing or invalid"));
+        let number = |key: &str| json[key].as_usize().ok_or_else(|| missing(key));
+        let millis = |key: &str| json[key].as_u64().map(Duration::from_millis).ok_or_else(|| missing(key));
+        let list = |value: &JsonValue, what: &str| -> Result<Vec<usize>, TetrisError> {
+            value.members().map(|member| member.as_usize().ok_or_else(|| missing(what))).collect()
+        };
+
+        let mode = json["mode"].as_str().and_then(Mode::parse).ok_or_else(|| missing("mode"))?;
+        let pieces = json["pieces"].as_str().and_then(PieceSet::parse).ok_or_else(|| missing("pieces"))?;
+        let randomizer = json["randomizer"].as_str().and_then(RandomizerKind::parse).ok_or_else(|| missing("randomizer"))?;
+        let mut game = GameState::with_pieces(pieces.shapes()?, randomizer.create());
+
+        // every index must point at one of the pieces, the custom set could have changed since the game was saved
+        let shapes = game.shapes.len();
+        let shape = |index: usize, what: &str| {
+            if index < shapes { Ok(index) } else { Err(TetrisError::Save(format!("{what} is not one of the {} pieces", pieces.name()))) }
+        };
+
+        if json["grid"].len() != BOARD_ROWS {
+            return Err(missing("grid"));
+        }
+        for (y, row) in json["grid"].members().enumerate() {
+            if row.len() != COLUMNS {
+                return Err(missing("grid"));
+            }
+            for (x, value) in row.members().enumerate() {
+                game.grid[y][x] = match value.as_i64().ok_or_else(|| missing("grid"))? {
+                    0 => continue,
+                    -1 => Cell::Garbage,
+                    value if value > 0 => Cell::Piece(shape(value as usize - 1, "grid")?),
+                    _ => return Err(missing("grid")),
+                };
+                game.bitboard.fill(x as i32, y as i32);
+            }
+        }
+
+        game.current = shape(number("current")?, "current")?;
+        game.upcoming = shape(number("upcoming")?, "upcoming")?;
+        game.held = match json["held"].as_usize() {
+            Some(held) => Some(shape(held, "held")?),
+            None if json["held"].is_null() => None,
+            None => return Err(missing("held")),
+        };
+        game.hold_used = json["hold_used"].as_bool().ok_or_else(|| missing("hold_used"))?;
+        game.player = match &json["player"] {
+            JsonValue::Null => None,
+            player => {
+                let coordinate = |key: &str| player[key].as_i32().ok_or_else(|| missing("player"));
+                let mut spawned = Player::spawn(coordinate("x")?, coordinate("y")?, game.shapes[game.current].spawn_orientation());
+                spawned.rotate(coordinate("rotation")?);
+                Some(spawned)
+            }
+        };
+        if game.collision().is_some() {
+            return Err(TetrisError::Save("the player is inside the stack".to_string()));
+        }
+
+        game.points = number("points")?;
+        game.lines = number("lines")?;
+        game.pieces = number("pieces_placed")?;
+        game.start_level = number("start_level")?;
+        game.piece_counts = list(&json["piece_counts"], "piece_counts")?;
+        game.piece_counts.resize(shapes, 0);
+        game.clearing = list(&json["clearing"], "clearing")?;
+        if game.clearing.iter().any(|&y| y >= BOARD_ROWS) {
+            return Err(missing("clearing"));
+        }
+        game.rng = json["rng"].as_str().and_then(|state| state.parse().ok()).map(GameRng::new).ok_or_else(|| missing("rng"))?;
+        let randomizer_state = list(&json["randomizer_state"], "randomizer_state")?;
+        for &index in &randomizer_state {
+            shape(index, "randomizer_state")?;
+        }
+        game.randomizer.restore(&randomizer_state);
+
+        Ok(SavedGame {
+            game,
+            user: User::add_user(json["user"].as_str().ok_or_else(|| missing("user"))?.to_string()),
+            mode,
+            pieces,
+            randomizer,
+            time: millis("time")?,
+            fall_timer: millis("fall_timer")?,
+            respawn_at: millis("respawn_at")?,
+            clear_at: millis("clear_at")?,
+            // games saved before practice games existed were all ranked
+            ranked: json["ranked"].as_bool().unwrap_or(true),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game in the middle of Sprint with a few locked pieces, something in hold and a seeded generator
+    fn saved_game() -> SavedGame {
+        let mut game = GameState::with_pieces(PieceSet::Classic.shapes().unwrap(), RandomizerKind::SevenBag.create());
+        game.rng = GameRng::new(42);
+        game.spawn();
+        game.hold();
+        for x in 0..COLUMNS - 1 {
+            game.grid[BOARD_ROWS - 1][x] = Cell::Garbage;
+            game.bitboard.fill(x as i32, BOARD_ROWS as i32 - 1);
+        }
+        game.grid[BOARD_ROWS - 2][0] = Cell::Piece(2);
+        game.bitboard.fill(0, BOARD_ROWS as i32 - 2);
+        game.points = 1200;
+        game.lines = 7;
+        game.pieces = 19;
+        SavedGame {
+            game,
+            user: User::add_user("Carl".to_string()),
+            mode: Mode::Sprint,
+            pieces: PieceSet::Classic,
+            randomizer: RandomizerKind::SevenBag,
+            time: Duration::from_millis(42_100),
+            fall_timer: Duration::from_millis(41_900),
+            respawn_at: Duration::ZERO,
+            clear_at: Duration::ZERO,
+            ranked: false,
+        }
+    }
+
+    #[test]
+    fn game_round_trips_through_json() {
+        let mut saved = saved_game();
+        let mut loaded = SavedGame::from_json(&saved.to_json()).unwrap();
+
+        assert_eq!(loaded.user.name(), "Carl");
+        assert!(matches!(loaded.mode, Mode::Sprint));
+        assert!(matches!(loaded.pieces, PieceSet::Classic));
+        assert_eq!(loaded.randomizer, RandomizerKind::SevenBag);
+        assert_eq!((loaded.time, loaded.fall_timer), (saved.time, saved.fall_timer));
+        assert!(!loaded.ranked);
+
+        let (game, loaded_game) = (&mut saved.game, &mut loaded.game);
+        assert!(game.grid == loaded_game.grid);
+        assert_eq!(game.bitboard, loaded_game.bitboard);
+        assert_eq!((game.current, game.upcoming, game.held, game.hold_used), (loaded_game.current, loaded_game.upcoming, loaded_game.held, loaded_game.hold_used));
+        assert_eq!((game.points, game.lines, game.pieces), (loaded_game.points, loaded_game.lines, loaded_game.pieces));
+        assert_eq!(game.piece_counts, loaded_game.piece_counts);
+        let player = game.player().unwrap();
+        let loaded_player = loaded_game.player().unwrap();
+        assert_eq!((player.position(), player.rotation()), (loaded_player.position(), loaded_player.rotation()));
+        assert_eq!(player.extent().collect::<Vec<_>>(), loaded_player.extent().collect::<Vec<_>>());
+
+        // the randomizer and its generator continue with the same pieces
+        for _ in 0..20 {
+            assert_eq!(game.next_shape_index(), loaded_game.next_shape_index());
+        }
+    }
+
+    #[test]
+    fn game_without_a_player_round_trips() {
+        let mut saved = saved_game();
+        saved.game.player = None;
+        let loaded = SavedGame::from_json(&saved.to_json()).unwrap();
+        assert!(loaded.game.player().is_none());
+    }
+
+    #[test]
+    fn missing_ranked_loads_as_ranked() {
+        let mut json = saved_game().to_json();
+        json.remove("ranked");
+        assert!(SavedGame::from_json(&json).unwrap().ranked);
+    }
+
+    #[test]
+    fn pieces_outside_the_piece_set_are_refused() {
+        let mut json = saved_game().to_json();
+        json["grid"][BOARD_ROWS - 3][0] = 8.into();
+        assert!(matches!(SavedGame::from_json(&json), Err(TetrisError::Save(_))));
+
+        let mut json = saved_game().to_json();
+        json["current"] = 7.into();
+        assert!(matches!(SavedGame::from_json(&json), Err(TetrisError::Save(_))));
+    }
+
+    #[test]
+    fn player_inside_the_stack_is_refused() {
+        // the first piece is picked before the generator is seeded, so the player must come from the same game as the json
+        let saved = saved_game();
+        let mut json = saved.to_json();
+        let (x, y) = saved.game.player().unwrap().extent().next().unwrap();
+        json["grid"][y as usize][x as usize] = (-1).into();
+        assert!(matches!(SavedGame::from_json(&json), Err(TetrisError::Save(_))));
+    }
+
+    #[test]
+    fn game_that_is_over_is_not_saved() {
+        let mut saved = saved_game();
+        // a hard drop that locks the piece above the visible rows, the game is over before the player quits
+        saved.game.try_move(PlayerMove::Translate(0, -(HIDDEN_ROWS as i32)));
+        assert!(saved.game.player().unwrap().extent().all(|(_, y)| y < HIDDEN_ROWS as i32));
+        saved.game.kill_player();
+        assert_eq!(saved.game.top_out(), Some(TopOut::LockOut));
+        assert!(matches!(saved.save(), Err(TetrisError::Save(_))));
+    }
+
+    #[test]
+    fn missing_fields_are_errors() {
+        for key in ["mode", "grid", "rng", "points", "hold_used"] {
+            let mut json = saved_game().to_json();
+            json.remove(key);
+            assert!(SavedGame::from_json(&json).is_err(), "{key}");
+        }
+    }
+}
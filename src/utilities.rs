@@ -1,6 +1,8 @@
 pub use rand::prelude::*;
 pub use std::time::{Duration, Instant};
 
+use crate::TetrisError;
+
 /// Denna enum delar upp spelar rörelser i två typer, rotering och flyttning.
 pub enum PlayerMove{
     Translate(i32, i32),
@@ -26,254 +28,197 @@ pub enum Collision{
 
 /// Denna struct representerar en form.
 /// 
-/// Den har en lista av a alla punkter som dess form inehavar och en punk som beskriver ett offset för vart den skal rotera.
+/// Den har en lista av alla punkter som formen inehavar för var och en av de fyra rotationerna, räknat från det övre vänstra hörnet av rutan den definerades i. Listorna räknas ut en gång när formen läses in, så att rotera bara är att byta lista (rotation) och inga decimaltal behövs.
+/// Den har ett namn (name), t.ex. 'T', och vet vilken färg i temat den ritas med (kind). kicks är vilken tabell av kicks som används när den roteras.
+/// spawn är kolumnen och raden (räknat från den översta synliga raden) som formen dyker upp på, och spawn_rotation hur många kvarts varv den roteras innan dess.
 #[derive(Clone)]
 pub struct Shape {
-    extent: Vec<(f32, f32)>,
-    offset: (f32, f32),
+    cells: [Vec<(i32, i32)>; 4],
+    rotation: usize,
+    spawn: (i32, i32),
+    spawn_rotation: i32,
+    kicks: Kicks,
+    name: char,
+    kind: usize,
+}
+
+/// Vilka kicks en form provar när den roteras
+/// 
+/// Tabellerna är:
+///     Srs: den vanliga tabellen i SRS, som används av J, L, S, T och Z
+///     I: I bitens egna tabell
+///     None: inga kicks, formen roteras bara om den får plats där den är (t.ex. O)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kicks {
+    Srs,
+    I,
+    None,
 }
 
+/// Offset tabellerna från SRS (Super Rotation System), en rad per rotation (spawn, höger, 180, vänster) med y uppåt.
+///
+/// Skillnaden mellan raden man roterar från och raden man roterar till är de förflyttningar (kicks) som provas i tur och ordning när en rotation krockar. Formerna roterar runt mitten av sin ruta, så skillnaden i första kolumnen dras av från alla kicks.
+const OFFSETS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const I_OFFSETS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
+    [(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
+    [(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
+    [(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
+];
+/// SRS har inga kicks för ett halvt varv, dessa är samma som i SRS+. En rad per rotation man roterar från, med y uppåt.
+const KICKS_180: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
 impl Shape {
-    /// Beskrivning: Denna funktion är till för att läsa in en textfil och sedan konvertera denna till en vector av Shape, men det visade sig vara onödigt kompliserat. det är snabbare att hårdkoda in värden istället.
+    /// Denna funktion läser in formerna i en bituppsättning, t.ex. en av filerna i src/pieces.
+    ///
+    /// Varje form börjar med en rad: namn färg kicks [rotation [kolumn rad]], sedan kommer en kvadratisk ruta där # är ett block och . är tomt. Formen roterar runt mitten av rutan. Om kolumn och rad saknas dyker formen upp centrerad (en kolumn åt vänster om den inte får plats exakt i mitten) med sitt översta block på den översta synliga raden. Tomma rader och rader som börjar med // hoppas över.
     /// 
-    /// Argument 1: &str - Detta är texten från en fil som definerar formerna
+    /// Argument 1(text): &str - Detta är texten från en fil som definerar formerna
+    /// Argument 2(source): &str - vilken bituppsättning texten kommer från, den står i felet om något är fel
     /// 
-    /// Return: Vec<Shape> - En lista av alla former (Shapes) som definerades i text filen
+    /// Return: Result<Vec<Shape>, TetrisError> - En lista av alla former (Shapes) som definerades i text filen, eller ett TetrisError::Data som säger vad som är fel i filen
     /// 
     /// Exempel: 
-    /// parse_shapes("###\n#   \n(1.0, 0.0)") -> [Shape { extent: [(-1.0, 0.0), (0.0, 0.0), (1.0, 0.0), (-1.0, 1.0)], offset: (1.0, 0.0) }]
-    /// parse_shapes("##\n##\n(0.5, 0.5)") -> [Shape { extent: [(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)], offset: (0.5, 0.5) }]
-    pub fn parse_shapes(_text: &str) -> Vec<Shape> {
-        // Warning. Denna funktion är inte implementerad som den ska. Just nu läser den inte filen, utan alla former är hårdkodade.
-        vec![
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (1.0, -1.0),
-                ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, -1.0),
-                    (0.0, -1.0),
-                    (1.0, -1.0),
-                    (-1.0, -0.0),
-                    (1.0, -0.0),
-                    (-1.0, 1.0),
-                    (1.0, 1.0),
-                    ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, -2.0),
-                    (0.0, -2.0),
-                    (1.0, -2.0),
-                    (-1.0, -1.0),
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (1.0, 1.0),
-                    (-1.0, 2.0),
-                    (0.0, 2.0),
-                    (1.0, 2.0),
-                    ],
-                offset: (1.0, 2.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (-1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    (1.0, 0.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    (1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, -1.0),
-                    (1.0, -1.0),
-                    ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (2.0, 0.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-0.5, -0.5),
-                    (0.5, -0.5),
-                    (-0.5, 0.5),
-                    (0.5, 0.5),
-                    ],
-                offset: (0.5, 0.5),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, -1.0),
-                    (0.0, -1.0),
-                    (1.0, -1.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (-1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    (1.0, 0.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    (1.0, 1.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (0.0, -1.0),
-                    (1.0, -1.0),
-                    ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, 0.0),
-                    (0.0, 0.0),
-                    (1.0, 0.0),
-                    (2.0, 0.0),
-                    ],
-                offset: (1.0, 0.0),
-            },
-            Shape {
-                extent: vec![
-                    (-0.5, -0.5),
-                    (0.5, -0.5),
-                    (-0.5, 0.5),
-                    (0.5, 0.5),
-                    ],
-                offset: (0.5, 0.5),
-            },
-            Shape {
-                extent: vec![
-                    (-1.0, -1.0),
-                    (0.0, -1.0),
-                    (1.0, -1.0),
-                    (0.0, 0.0),
-                    (0.0, 1.0),
-                    ],
-                offset: (1.0, 1.0),
-            },
-            Shape {
-                extent: vec![
-                    (-0.5, -0.5),
-                    (0.5, -0.5),
-                    (-0.5, 0.5),
-                    (0.5, 0.5),
-                    (1.5, 0.5)
-                    ],
-                offset: (0.5, 0.5),
-            },
-            Shape {
-                extent: vec![
-                    (-0.5, -0.5),
-                    (0.5, -0.5),
-                    (-0.5, 0.5),
-                    (0.5, 0.5),
-                    (1.5, -0.5)
-                    ],
-                offset: (0.5, 0.5),
-            },
-        ]
-    }
-
-    /// Ännu en så kallad "getter" som låter ossa komma åt ett värde hos struct som igentligen är privat.
-    /// 
-    /// Argument 1: &self - Shape structen
-    /// 
-    /// Return: (f32, f32) - formens offset.
-    /// 
+    ///     parse_shapes("T 2 srs\n.#.\n###\n...", "Custom pieces") -> Ok([Shape { cells: [[(1, 0), (0, 1), (1, 1), (2, 1)], [(1, 0), (1, 1), (2, 1), (1, 2)], ..], rotation: 0, spawn: (6, 0), name: 'T', kind: 2, .. }])
+    ///     parse_shapes("O 6 none\n##\n##", "Custom pieces") -> Ok([Shape { cells: [[(0, 0), (1, 0), (0, 1), (1, 1)], ..], rotation: 0, spawn: (7, 0), name: 'O', kind: 6, .. }])
+    ///     parse_shapes("T 2 srs\n.#.\n###", "Custom pieces") -> Err(TetrisError::Data("Custom pieces", "line 3: the box of T has 2 rows but should have 3"))
+    pub fn parse_shapes(text: &str, source: &str) -> Result<Vec<Shape>, TetrisError> {
+        let error = |message: String| TetrisError::Data(source.to_string(), message);
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with("//"))
+            .peekable();
+        let mut shapes = Vec::new();
+        while let Some((number, header)) = lines.next() {
+            let fields: Vec<&str> = header.split_whitespace().collect();
+            let mut name = fields[0].chars();
+            let name = match (name.next(), name.next()) {
+                (Some(name), None) => name,
+                _ => return Err(error(format!("line {number}: the name of a piece should be one character, not {}", fields[0]))),
+            };
+            let number_at = |index: usize, what: &str| -> Result<Option<i32>, TetrisError> {
+                match fields.get(index) {
+                    Some(field) => field.parse().map(Some).map_err(|_| error(format!("line {number}: the {what} of {name} is not a number"))),
+                    None => Ok(None),
+                }
+            };
+            let kind = number_at(1, "color")?.ok_or_else(|| error(format!("line {number}: {name} has no color")))?;
+            let kicks = match fields.get(2) {
+                Some(&"srs") => Kicks::Srs,
+                Some(&"i") => Kicks::I,
+                Some(&"none") => Kicks::None,
+                _ => return Err(error(format!("line {number}: the kicks of {name} should be srs, i or none"))),
+            };
+            let spawn_rotation = number_at(3, "rotation")?.unwrap_or(0);
+            let spawn = match (number_at(4, "column")?, number_at(5, "row")?) {
+                (Some(column), Some(row)) => Some((column, row)),
+                (None, None) => None,
+                _ => return Err(error(format!("line {number}: {name} needs both a column and a row to spawn on"))),
+            };
+
+            let mut rows = Vec::new();
+            while let Some((_, row)) = lines.next_if(|(_, line)| line.chars().all(|char| char == '#' || char == '.')) {
+                rows.push(row);
+            }
+            let size = rows.first().map(|row| row.chars().count()).unwrap_or(0);
+            if size == 0 {
+                return Err(error(format!("line {}: {name} has no box", number + 1)));
+            }
+            if rows.len() != size || rows.iter().any(|row| row.chars().count() != size) {
+                return Err(error(format!("line {}: the box of {name} has {} rows but should have {size}", number + rows.len(), rows.len())));
+            }
+            let extent: Vec<(i32, i32)> = rows
+                .iter()
+                .enumerate()
+                .flat_map(|(y, row)| row.chars().enumerate().filter(|(_, char)| *char == '#').map(move |(x, _)| (x as i32, y as i32)))
+                .collect();
+            if extent.is_empty() {
+                return Err(error(format!("line {number}: {name} has no blocks")));
+            }
+            // ett kvarts varv medsols runt mitten av rutan flyttar (x, y) till (size - 1 - y, x)
+            let last = size as i32 - 1;
+            let mut cells: [Vec<(i32, i32)>; 4] = Default::default();
+            cells[0] = extent;
+            for rotation in 1..4 {
+                cells[rotation] = cells[rotation - 1].iter().map(|&(x, y)| (last - y, x)).collect();
+            }
+            let mut shape = Shape {
+                cells,
+                rotation: 0,
+                spawn: (0, 0),
+                spawn_rotation,
+                kicks,
+                name,
+                kind: kind.max(0) as usize,
+            };
+            shape.spawn = spawn.unwrap_or_else(|| {
+                let cells = shape.orientation(spawn_rotation);
+                let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+                let max_x = cells.iter().map(|&(x, _)| x).max().unwrap_or(0);
+                let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+                ((crate::COLUMNS as i32 - (max_x - min_x + 1)) / 2 - min_x, -min_y)
+            });
+            shapes.push(shape);
+        }
+        Ok(shapes)
+    }
+
+    /// En getter för var formen dyker upp, som (kolumn, rad) där rad 0 är den översta synliga raden.
+    ///
+    /// Exempel:
+    ///     shape.spawn() -> (6, 1)
+    pub fn spawn(&self) -> (i32, i32) {
+        self.spawn
+    }
+
+    /// Denna funktion ger formen roterad så som den dyker upp på spelplanen, t.ex. för att visa den i sidopanelen.
+    ///
+    /// Exempel:
+    ///     t_shape.spawn_orientation() -> en T som pekar uppåt
+    pub fn spawn_orientation(&self) -> Shape {
+        let mut shape = self.clone();
+        shape.rotation = self.spawn_rotation.rem_euclid(4) as usize;
+        shape
+    }
+
+    /// En getter för hur många kvarts varv medsols formen roteras innan den dyker upp.
+    ///
+    /// Exempel:
+    ///     shape.spawn_rotation() -> 0
+    pub fn spawn_rotation(&self) -> i32 {
+        self.spawn_rotation
+    }
+
+    /// En getter för formens namn.
+    ///
     /// Exempel:
-    ///     shape.get_offset() -> (0.5, 0.5)
-    ///     shape.get_offset() -> (1.0, 0.0)
-    pub fn get_offset(&self) -> (f32, f32) {
-        (self.offset.0, self.offset.1)
+    ///     shape.name() -> 'T'
+    pub fn name(&self) -> char {
+        self.name
     }
 
-    /// Denna funktion roterar formen, eftersom vi endast roterar 90 grader i taget är denna kod mycket simpel. Vi byter bara platts på x och y och gör ena negativ. Bam. färdigt.
+    /// En getter för vilken färg i temat formen ritas med.
+    ///
+    /// Exempel:
+    ///     shape.kind() -> 0
+    ///     shape.kind() -> 6
+    pub fn kind(&self) -> usize {
+        self.kind
+    }
+
+    /// Denna funktion roterar formen. Alla rotationer är redan uträknade, så vi byter bara vilken av dem som används. Bam. färdigt.
     /// 
     /// Argument 1(self): &mut self - en muterbar reference till formen
     /// Argument 2(angle): i32 - hur många 90 graders rotationer som skal göras
@@ -284,30 +229,63 @@ impl Shape {
     ///     self.rotate(-5);
     ///     self.rotate(69);
     pub fn rotate(&mut self, angle: i32) {
-        for (x, y) in self.extent.iter_mut() {
-            let (x2, y2) = match angle.rem_euclid(4) {
-                1 => (-*y, *x),
-                2 => (-*x, -*y),
-                3 => (*y, -*x),
-                _ => (*x, *y),
-            };
-            *x = x2;
-            *y = y2;
-        }
+        self.rotation = (self.rotation as i32 + angle).rem_euclid(4) as usize;
     }
 
-    /// Denna funktion ger tillbaka listan över alla punkter som formen innehavar, denna funktion lägger även på offsetet.
+    /// Denna funktion ger tillbaka listan över alla punkter som formen innehavar i sin nuvarande rotation. Listan lånas ut, så inget behöver kopieras.
     /// 
     /// Argument 1(self): &self - en icke muterbar reference till en Shape instans
     /// 
-    /// Return: Vec<(i32, i32)> - listan över alla punkter som former innehavar med inräknat offset.
+    /// Return: &[(i32, i32)] - listan över alla punkter som former innehavar, räknat från rutans övre vänstra hörn.
     /// 
     /// Exempel:
     ///     self.extent() -> [(0, 0), (1, 0), (2, 0), (0, 1)]
     ///     self.extent() -> [(0, 0), (1, 0), (2, 0), (1, 1), (1, 2)]
     ///     self.extent() -> [(0, 0), (1, 0), (0, 1), (1, 1)]
-    pub fn extent(&self) -> Vec<(i32, i32)> {
-        self.extent.clone().iter().map(|(x, y)| ((*x + self.offset.0) as i32, (*y + self.offset.1) as i32)).collect()
+    pub fn extent(&self) -> &[(i32, i32)] {
+        &self.cells[self.rotation]
+    }
+
+    /// Denna funktion ger punkterna för en viss rotation utan att rotera formen, t.ex. för en bot som provar alla rotationer.
+    ///
+    /// Argument 1(self): &self - formen
+    /// Argument 2(rotation): i32 - hur många kvarts varv medsols från hur formen definerades
+    ///
+    /// Exempel:
+    ///     t_shape.orientation(1) -> [(1, 0), (1, 1), (2, 1), (1, 2)]
+    ///     t_shape.orientation(-3) -> [(1, 0), (1, 1), (2, 1), (1, 2)]
+    pub fn orientation(&self, rotation: i32) -> &[(i32, i32)] {
+        &self.cells[rotation.rem_euclid(4) as usize]
+    }
+
+    /// Denna funktion ger de förflyttningar (kicks) som ska provas när formen roteras, i den ordning de ska provas. Den första som inte krockar används.
+    ///
+    /// Argument 1(self): &self - formen som roteras
+    /// Argument 2(from): i32 - rotationen formen har innan, 0 är spawn och 1 är ett kvarts varv medsols
+    /// Argument 3(angle): i32 - hur många 90 graders rotationer som görs
+    ///
+    /// Return: Vec<(i32, i32)> - förflyttningarna i spelplanens koordinater, alltså med y nedåt
+    ///
+    /// Exempel:
+    ///     t_shape.kicks(0, 1) -> [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]
+    ///     t_shape.kicks(0, 2) -> [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)]
+    pub fn kicks(&self, from: i32, angle: i32) -> Vec<(i32, i32)> {
+        let offsets = match self.kicks {
+            Kicks::Srs => &OFFSETS,
+            Kicks::I => &I_OFFSETS,
+            Kicks::None => return vec![(0, 0)],
+        };
+        let from = from.rem_euclid(4) as usize;
+        let to = (from as i32 + angle).rem_euclid(4) as usize;
+        let kicks: Vec<(i32, i32)> = if to == from {
+            vec![(0, 0)]
+        } else if (to + 2) % 4 == from {
+            KICKS_180[from].to_vec()
+        } else {
+            let (x, y) = (offsets[from][0].0 - offsets[to][0].0, offsets[from][0].1 - offsets[to][0].1);
+            offsets[from].iter().zip(offsets[to].iter()).map(|(a, b)| (a.0 - b.0 - x, a.1 - b.1 - y)).collect()
+        };
+        kicks.into_iter().map(|(x, y)| (x, -y)).collect()
     }
 }
 
@@ -317,12 +295,12 @@ impl Shape {
 ///     x: spelarens x position
 ///     y: spelarens y position
 ///     shape: spelarens nuvarande form.
-///     coloe: färd indexet som spelaren har
+///     rotation: hur många kvarts varv medsols formen har roterats sedan den spawnade, 0 till 3
 pub struct Player {
     x: i32,
     y: i32,
     shape: Shape,
-    pub color: usize,
+    rotation: i32,
 }
 
 impl Player {
@@ -333,20 +311,24 @@ impl Player {
     /// Argument 1(x): i32 - spelarens x position
     /// Argument 2(y): i32 - spelarens y position
     /// Argument 3(shape): Shape - spelarens nuvarande funktion
-    /// Argument 4(color): usize - indexet för färgen som spelaren har
     /// 
     /// Return: Player - en ny instans av en spelare
     /// 
     /// Exempel:
-    ///     Player::spawn(1, 2, Shape { extent: [(0.0, 0.0),], offset: (0.0, 0.0) }, 69) -> Player { x: 1, y: 2, shape: Shape { extent: [(0.0, 0.0),], offset: (0.0, 0.0) }, color: 69}
-    pub fn spawn(x:i32, y:i32,shape:Shape,color:usize) -> Self {
+    ///     Player::spawn(1, 2, Shape { cells: [[(0, 0),], ..], name: 'O', kind: 6, .. }) -> Player { x: 1, y: 2, shape: Shape { cells: [[(0, 0),], ..], name: 'O', kind: 6, .. }, rotation: 0 }
+    pub fn spawn(x:i32, y:i32,shape:Shape) -> Self {
         Self {
             x,
             y,
             shape,
-            color,
+            rotation: 0,
         }
     }
+
+    /// En getter för vilken sorts bit spelaren är. Det är denna som bestämmer spelarens färg.
+    pub fn kind(&self) -> usize {
+        self.shape.kind()
+    }
     
     /// Denna funktion roterar spelare. Den roterar alltså spelarens shape.
     /// 
@@ -360,6 +342,28 @@ impl Player {
     ///     self.rotate(420);
     pub fn rotate(&mut self, angle: i32) {
         self.shape.rotate(angle);
+        self.rotation = (self.rotation + angle).rem_euclid(4);
+    }
+
+    /// En getter för spelarens position, som (x, y) på hela spelplanen.
+    ///
+    /// Exempel:
+    ///     player.position() -> (6, 4)
+    pub fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// En getter för hur många kvarts varv medsols spelaren har roterats sedan den spawnade.
+    ///
+    /// Exempel:
+    ///     player.rotation() -> 3
+    pub fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Denna funktion ger förflyttningarna (kicks) som provas när spelaren roteras från sin nuvarande rotation, se Shape::kicks.
+    pub fn kicks(&self, angle: i32) -> Vec<(i32, i32)> {
+        self.shape.kicks(self.rotation, angle)
     }
 
     /// Denna funktion förflyttar spelaren. Den muterar helt enkelt x och y värdet hos spelaren.
@@ -378,54 +382,195 @@ impl Player {
         self.y += dy;
     }
 
-    /// Denna funktion ger oss alla positioner som spelarens form innehavar. Denna tar hänsyn till spelarens position och är i ett globalt perspektiv.
+    /// Denna funktion ger oss alla positioner som spelarens form innehavar. Denna tar hänsyn till spelarens position och är i ett globalt perspektiv. Punkterna räknas ut medan man går igenom dem, så inget allokeras (viktigt när kollisioner kollas många gånger).
     /// 
     /// Argument 1(self): Player - en icke muterbar reference till spelaren
     /// 
-    /// Return: Vec<(i32, i32)> - en vector över alla punkter som spelaren innehavar
+    /// Return: impl Iterator<Item = (i32, i32)> + Clone - alla punkter som spelaren innehavar
     /// 
     /// Exempel:
     ///     self.extent() -> [(0, 0), (1, 0), (2, 0), (0, 1)]
     ///     self.extent() -> [(0, 0), (1, 0), (2, 0), (1, 1), (1, 2)]
     ///     self.extent() -> [(0, 0), (1, 0), (0, 1), (1, 1)]
-    pub fn extent(&self) -> Vec<(i32, i32)> {
-        self.shape.extent().into_iter().map(|(x, y)| (x + self.x, y + self.y)).collect()
+    pub fn extent(&self) -> impl Iterator<Item = (i32, i32)> + Clone + '_ {
+        self.shape.extent().iter().map(|&(x, y)| (x + self.x, y + self.y))
     }
 }
 
-/// Denna struct är till för att simplifiera mätning av fps (Frames Per Second)
-/// 
-/// Den ser hur många bilder det framkallas under en viss tid och spara detta i dess publika fält fps.
-pub struct Fps {
-    time: Instant,
-    frames: u64,
-    pub fps: f64, /// Detta fält
-    measurement_time: Duration,
+/// Denna struct är spelets klocka
+///
+/// Den mäter hur länge spelet har pågått, men står still medan spelet är pausat. Allt som ska följa spelets tid (fall, respawn och animationer) använder denna istället för Instant, då behöver ingenting flyttas fram efter en paus.
+/// offset är tiden som klockan startade på, den läggs till istället för att start flyttas bakåt eftersom en Instant inte kan gå längre bakåt än till när datorn startade.
+pub struct Clock {
+    start: Instant,
+    paused_at: Option<Instant>,
+    paused: Duration,
+    offset: Duration,
 }
 
-impl Fps {
-    /// Skapar en ny fps instans med grund värden och specifecerar mättiden i funktionens parameter
-    pub fn new(measurement_time: Duration) -> Fps {
-        Fps {
-            time: Instant::now(),
-            frames: 0,
-            fps: 0.0,
-            measurement_time,
+impl Clock {
+    /// Skapar en ny klocka som börjar på noll
+    pub fn new() -> Self {
+        Self::starting_at(Duration::ZERO)
+    }
+
+    /// Skapar en klocka som redan visar en viss tid, t.ex. när ett sparat spel fortsätter.
+    ///
+    /// Exempel:
+    ///     Clock::starting_at(Duration::from_secs(30)).now() -> 30s
+    pub fn starting_at(elapsed: Duration) -> Self {
+        Clock {
+            start: Instant::now(),
+            paused_at: None,
+            paused: Duration::ZERO,
+            offset: elapsed,
         }
     }
 
-    /// Startar om mätningen för att se hur fps förändras med tid. Ett snitt över hela körtiden på programmet är inte hjälpsamt så fps:n ändras drastigkt under spelets gång.
-    fn reset(&mut self) {
-        self.time = Instant::now();
-        self.frames = 0;
+    /// Denna funktion ger hur länge spelet har pågått, utan tiden det har varit pausat.
+    ///
+    /// Exempel:
+    ///     clock.now() -> 12.5s
+    pub fn now(&self) -> Duration {
+        let end = self.paused_at.unwrap_or_else(Instant::now);
+        end.duration_since(self.start).saturating_sub(self.paused) + self.offset
+    }
+
+    /// Denna funktion ger spelets tid vid ett visst ögonblick, t.ex. när en knapp trycktes.
+    ///
+    /// Exempel:
+    ///     clock.at(Instant::now()) -> 12.5s
+    pub fn at(&self, instant: Instant) -> Duration {
+        self.now().saturating_sub(instant.elapsed())
+    }
+
+    /// Stoppar klockan. Att pausa en klocka som redan är pausad gör ingenting.
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Startar klockan igen efter en paus.
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += paused_at.elapsed();
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(text: &str) -> Shape {
+        Shape::parse_shapes(text, "test pieces").unwrap().remove(0)
+    }
+
+    #[test]
+    fn pieces_spawn_centered_with_their_top_block_on_the_top_row() {
+        let t = shape("T 2 srs\n.#.\n###\n...");
+        assert_eq!(t.spawn(), (6, 0));
+        assert_eq!(t.spawn_rotation(), 0);
+        // I ligger på andra raden i sin ruta, så den flyttas upp en rad
+        let i = shape("I 5 i\n....\n####\n....\n....");
+        assert_eq!(i.spawn(), (6, -1));
+        let o = shape("O 6 none\n##\n##");
+        assert_eq!(o.spawn(), (7, 0));
+        // en form som inte får plats exakt i mitten hamnar en kolumn åt vänster
+        let l = shape("L 3 srs\n#..\n###\n...");
+        assert_eq!(l.spawn(), (6, 0));
+        let five = shape("F 4 none\n#####\n.....\n.....\n.....\n.....");
+        assert_eq!(five.spawn(), (5, 0));
     }
 
-    /// Inkrementerar frame fältet med ett och kollar om tillräckligt lång tid har gått för att utföra beräkning och för att återställa räkningen.
-    pub fn frame(&mut self) {
-        self.frames += 1;
-        if self.time.elapsed() > self.measurement_time {
-            self.fps = (self.frames as f64)/self.time.elapsed().as_secs_f64();
-            self.reset();
+    #[test]
+    fn spawn_rotation_turns_the_piece_before_it_is_centered() {
+        let t = shape("T 2 srs 2\n.#.\n###\n...");
+        assert_eq!(t.spawn_rotation(), 2);
+        assert_eq!(t.spawn_orientation().extent(), t.orientation(2));
+        assert_eq!(t.extent(), t.orientation(0));
+        // efter ett halvt varv är T:s översta block på andra raden i rutan
+        assert_eq!(t.spawn(), (6, -1));
+
+        let i = shape("I 5 i 1\n....\n####\n....\n....");
+        assert_eq!(i.spawn_orientation().extent(), &[(2, 0), (2, 1), (2, 2), (2, 3)]);
+        assert_eq!(i.spawn(), (5, 0));
+
+        let j = shape("J 1 srs -1\n#..\n###\n...");
+        assert_eq!(j.spawn_orientation().extent(), j.orientation(3));
+    }
+
+    #[test]
+    fn spawn_column_and_row_can_be_given() {
+        let t = shape("T 2 srs 1 3 5\n.#.\n###\n...");
+        assert_eq!(t.spawn(), (3, 5));
+        assert_eq!(t.spawn_rotation(), 1);
+        assert!(Shape::parse_shapes("T 2 srs 1 3\n.#.\n###\n...", "test pieces").is_err());
+        assert!(Shape::parse_shapes("T 2 srs up\n.#.\n###\n...", "test pieces").is_err());
+    }
+
+    /// Kick tabellerna från SRS med y nedåt, (från, till, kicks)
+    #[test]
+    fn srs_kicks_match_the_guideline() {
+        let t = shape("T 2 srs\n.#.\n###\n...");
+        let table = [
+            (0, 1, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+            (1, 0, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+            (1, 2, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+            (2, 1, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+            (2, 3, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+            (3, 2, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+            (3, 0, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+            (0, 3, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+        ];
+        for (from, to, kicks) in table {
+            let angle = if (from + 1) % 4 == to { 1 } else { -1 };
+            assert_eq!(t.kicks(from, angle), kicks, "{from} -> {to}");
         }
     }
+
+    #[test]
+    fn i_kicks_match_the_guideline() {
+        let i = shape("I 5 i\n....\n####\n....\n....");
+        let table = [
+            (0, 1, [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]),
+            (1, 0, [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)]),
+            (1, 2, [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]),
+            (2, 1, [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)]),
+            (2, 3, [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)]),
+            (3, 2, [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]),
+            (3, 0, [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)]),
+            (0, 3, [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]),
+        ];
+        for (from, to, kicks) in table {
+            let angle = if (from + 1) % 4 == to { 1 } else { -1 };
+            assert_eq!(i.kicks(from, angle), kicks, "{from} -> {to}");
+        }
+    }
+
+    #[test]
+    fn half_turns_use_their_own_table() {
+        let t = shape("T 2 srs\n.#.\n###\n...");
+        assert_eq!(t.kicks(0, 2), vec![(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)]);
+        assert_eq!(t.kicks(3, -2), vec![(0, 0), (-1, 0), (-1, -2), (-1, -1), (0, -2), (0, -1)]);
+    }
+
+    #[test]
+    fn shapes_without_kicks_only_try_where_they_are() {
+        let o = shape("O 6 none\n##\n##");
+        assert_eq!(o.kicks(0, 1), vec![(0, 0)]);
+        assert_eq!(o.kicks(2, 2), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn full_turns_do_not_kick() {
+        let t = shape("T 2 srs\n.#.\n###\n...");
+        assert_eq!(t.kicks(1, 4), vec![(0, 0)]);
+        assert_eq!(t.kicks(1, -4), vec![(0, 0)]);
+    }
 }